@@ -0,0 +1,310 @@
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use std::{
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+use rand::rngs::OsRng;
+use tokio::sync::Semaphore;
+
+use crate::{db::normalize_slug, errors::Error};
+
+/// How long a browser may read a protected paste after entering its view password.
+pub const VIEW_COOKIE_MAX_AGE_SECS: i64 = 60 * 60;
+
+/// The outcome of checking an edit code against the one stored for a slug.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditCodeMatch {
+    /// The code matches a hashed edit code.
+    Valid,
+    /// The code matches an edit code stored in plaintext by an older version,
+    /// which should be replaced by its hash.
+    ValidLegacy,
+    Invalid,
+}
+
+/// Hashes an edit code with Argon2id and a random per-record salt.
+///
+/// Returns the hash in PHC string format, which embeds the salt and parameters.
+pub fn hash_edit_code(edit_code: &str) -> Result<String, Error> {
+    hash_secret(edit_code)
+}
+
+/// Hashes the password needed to read a paste, the same way as edit codes.
+pub fn hash_view_password(password: &str) -> Result<String, Error> {
+    hash_secret(password)
+}
+
+/// Runs Argon2 work on the blocking thread pool. Hashing takes tens of
+/// milliseconds of CPU on purpose, which would hold up every other request on
+/// the same executor thread.
+pub async fn spawn_argon2<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> T {
+    match tokio::task::spawn_blocking(work).await {
+        Ok(value) => value,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+fn hash_secret(secret: &str) -> Result<String, Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default().hash_password(secret.as_bytes(), &salt)?;
+    Ok(hash.to_string())
+}
+
+/// Checks an edit code against the stored one without leaking timing information.
+///
+/// Edit codes are at most 32 characters long while a PHC string is much
+/// longer, so a stored value that parses as a PHC string is always a hash.
+pub fn verify_edit_code(edit_code: &str, stored: &str) -> EditCodeMatch {
+    match PasswordHash::new(stored) {
+        Ok(hash) => match Argon2::default().verify_password(edit_code.as_bytes(), &hash) {
+            Ok(()) => EditCodeMatch::Valid,
+            Err(_) => EditCodeMatch::Invalid,
+        },
+        // blake3 hashes compare in constant time
+        Err(_) if blake3::hash(edit_code.as_bytes()) == blake3::hash(stored.as_bytes()) => {
+            EditCodeMatch::ValidLegacy
+        }
+        Err(_) => EditCodeMatch::Invalid,
+    }
+}
+
+/// Returns whether a stored edit code is a plaintext code from an older version.
+pub fn is_legacy_edit_code(stored: &str) -> bool {
+    PasswordHash::new(stored).is_err()
+}
+
+/// Checks a view password against the stored hash.
+pub fn verify_view_password(password: &str, stored: &str) -> bool {
+    PasswordHash::new(stored).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// Wrong view passwords a paste takes within [`WRONG_VIEW_PASSWORD_WINDOW`]
+/// before it stops checking them.
+pub const MAX_WRONG_VIEW_PASSWORDS: u32 = 10;
+
+pub const WRONG_VIEW_PASSWORD_WINDOW: Duration = Duration::from_secs(60);
+
+/// How many slugs wrong view passwords are counted for, least recently guessed
+/// ones being forgotten first.
+const TRACKED_SLUGS: usize = 10_000;
+
+/// The outcome of [`ViewPasswordGuard::check`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewPasswordCheck {
+    Correct,
+    Wrong,
+    /// Too many wrong passwords were tried for the paste lately, so this one
+    /// was not checked.
+    Throttled,
+}
+
+/// Checks view passwords, which anyone can send, without letting them tie up
+/// the server or be guessed at leisure.
+///
+/// Only as many checks run at once as there are CPUs, and a paste stops
+/// checking passwords for a while after [`MAX_WRONG_VIEW_PASSWORDS`] wrong ones.
+#[derive(Debug, Clone)]
+pub struct ViewPasswordGuard {
+    checks: Arc<Semaphore>,
+    wrong: Arc<Mutex<LruCache<String, WrongPasswords>>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct WrongPasswords {
+    since: Instant,
+    count: u32,
+}
+
+impl Default for ViewPasswordGuard {
+    fn default() -> Self {
+        let parallelism = std::thread::available_parallelism().map_or(4, NonZeroUsize::get);
+        let tracked = NonZeroUsize::new(TRACKED_SLUGS).expect("TRACKED_SLUGS is not zero");
+        Self {
+            checks: Arc::new(Semaphore::new(parallelism)),
+            wrong: Arc::new(Mutex::new(LruCache::new(tracked))),
+        }
+    }
+}
+
+impl ViewPasswordGuard {
+    /// Checks `password` against `stored`, the hash kept for the paste at `slug`.
+    pub async fn check(&self, slug: &str, password: &str, stored: &str) -> ViewPasswordCheck {
+        let key = normalize_slug(slug);
+        if self.is_throttled(&key) {
+            return ViewPasswordCheck::Throttled;
+        }
+
+        let _permit = self
+            .checks
+            .acquire()
+            .await
+            .expect("the semaphore is never closed");
+        // others may have used up the guesses while this one waited
+        if self.is_throttled(&key) {
+            return ViewPasswordCheck::Throttled;
+        }
+
+        let (password, stored) = (password.to_string(), stored.to_string());
+        if spawn_argon2(move || verify_view_password(&password, &stored)).await {
+            return ViewPasswordCheck::Correct;
+        }
+
+        let mut wrong = self.lock();
+        match wrong.get_mut(&key) {
+            Some(wrong) if wrong.since.elapsed() < WRONG_VIEW_PASSWORD_WINDOW => wrong.count += 1,
+            _ => {
+                wrong.put(
+                    key,
+                    WrongPasswords {
+                        since: Instant::now(),
+                        count: 1,
+                    },
+                );
+            }
+        }
+        ViewPasswordCheck::Wrong
+    }
+
+    fn is_throttled(&self, key: &str) -> bool {
+        self.lock().peek(key).is_some_and(|wrong| {
+            wrong.since.elapsed() < WRONG_VIEW_PASSWORD_WINDOW
+                && wrong.count >= MAX_WRONG_VIEW_PASSWORDS
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LruCache<String, WrongPasswords>> {
+        // the counts hold no invariants a panic could break
+        self.wrong.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Name of the cookie that lets a browser read the protected paste at `slug`.
+pub fn view_cookie_name(slug: &str) -> String {
+    format!("view_{}", normalize_slug(slug))
+}
+
+/// Signs a grant to read the paste at `slug` until `expires`.
+///
+/// The grant is tied to the stored password hash, so it stops working as soon
+/// as the paste is deleted or its slug is taken by another paste.
+pub fn sign_view_cookie(
+    key: &[u8; 32],
+    slug: &str,
+    password_hash: &str,
+    expires: DateTime<Utc>,
+) -> String {
+    let expires = expires.timestamp();
+    let mac = view_cookie_mac(key, slug, password_hash, expires);
+    format!("{expires}.{}", mac.to_hex())
+}
+
+/// Checks a grant made by [`sign_view_cookie`], which must not have expired.
+pub fn verify_view_cookie(key: &[u8; 32], slug: &str, password_hash: &str, value: &str) -> bool {
+    let Some((expires, mac)) = value.split_once('.') else {
+        return false;
+    };
+    let (Ok(expires), Ok(mac)) = (expires.parse::<i64>(), blake3::Hash::from_hex(mac)) else {
+        return false;
+    };
+
+    // blake3 hashes compare in constant time
+    expires > Utc::now().timestamp() && mac == view_cookie_mac(key, slug, password_hash, expires)
+}
+
+fn view_cookie_mac(key: &[u8; 32], slug: &str, password_hash: &str, expires: i64) -> blake3::Hash {
+    let mut hasher = blake3::Hasher::new_keyed(key);
+    hasher.update(normalize_slug(slug).as_bytes());
+    hasher.update(&[0]);
+    hasher.update(password_hash.as_bytes());
+    hasher.update(&[0]);
+    hasher.update(&expires.to_be_bytes());
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_codes_are_hashed_with_a_salt() {
+        let hash = hash_edit_code("correct-code").unwrap();
+        assert!(hash.starts_with("$argon2id$"), "{hash}");
+        assert_ne!(hash, hash_edit_code("correct-code").unwrap());
+        assert!(!is_legacy_edit_code(&hash));
+
+        assert_eq!(
+            verify_edit_code("correct-code", &hash),
+            EditCodeMatch::Valid
+        );
+        assert_eq!(
+            verify_edit_code("wrong-code", &hash),
+            EditCodeMatch::Invalid
+        );
+    }
+
+    #[test]
+    fn plaintext_edit_codes_are_recognized() {
+        assert!(is_legacy_edit_code("plain-code"));
+        assert_eq!(
+            verify_edit_code("plain-code", "plain-code"),
+            EditCodeMatch::ValidLegacy
+        );
+        assert_eq!(
+            verify_edit_code("plain-cod", "plain-code"),
+            EditCodeMatch::Invalid
+        );
+        assert_eq!(verify_edit_code("", "plain-code"), EditCodeMatch::Invalid);
+    }
+
+    #[test]
+    fn view_passwords_are_verified_against_their_hash() {
+        let hash = hash_view_password("open sesame").unwrap();
+        assert!(verify_view_password("open sesame", &hash));
+        assert!(!verify_view_password("open says me", &hash));
+        // view passwords never had a plaintext form
+        assert!(!verify_view_password("open sesame", "open sesame"));
+    }
+
+    #[tokio::test]
+    async fn wrong_view_passwords_are_throttled_per_slug() {
+        let guard = ViewPasswordGuard::default();
+        let hash = hash_view_password("open sesame").unwrap();
+
+        assert_eq!(
+            guard.check("Locked", "open sesame", &hash).await,
+            ViewPasswordCheck::Correct
+        );
+        for _ in 0..MAX_WRONG_VIEW_PASSWORDS {
+            assert_eq!(
+                guard.check("locked", "guess", &hash).await,
+                ViewPasswordCheck::Wrong
+            );
+        }
+        assert_eq!(
+            guard.check("LOCKED", "open sesame", &hash).await,
+            ViewPasswordCheck::Throttled
+        );
+        assert_eq!(
+            guard.check("other", "open sesame", &hash).await,
+            ViewPasswordCheck::Correct
+        );
+    }
+
+    #[tokio::test]
+    async fn argon2_runs_off_the_executor() {
+        let hash = spawn_argon2(|| hash_edit_code("code")).await.unwrap();
+        let matched = spawn_argon2(move || verify_edit_code("code", &hash)).await;
+        assert_eq!(matched, EditCodeMatch::Valid);
+    }
+}
@@ -0,0 +1,136 @@
+use std::sync::{Arc, RwLock};
+
+use chrono::Utc;
+use log::warn;
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    db::{BlockRule, Database, PatternKind},
+    errors::Error,
+};
+
+/// Slugs that would collide with routes or be mistaken for part of the site.
+pub const RESERVED_SLUGS: &[&str] = &[
+    "admin", "api", "diff", "edit", "help", "index", "login", "logout", "new", "p", "raw",
+    "revisions", "settings", "static",
+];
+
+/// Whether `slug` is one of [`RESERVED_SLUGS`], ignoring case.
+pub fn is_reserved_slug(slug: &str) -> bool {
+    RESERVED_SLUGS
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(slug))
+}
+
+/// A blocklist entry along with the pattern it was stored under.
+#[derive(Debug, Clone)]
+pub struct BlockEntry {
+    pub pattern: String,
+    pub rule: BlockRule,
+}
+
+/// The slug patterns an admin has blocked, compiled once and kept in memory.
+///
+/// The `blocklist` tree is the source of truth; every change goes through
+/// here so the compiled patterns never lag behind it.
+#[derive(Debug, Clone)]
+pub struct Blocklist {
+    entries: Arc<RwLock<Vec<(BlockEntry, Regex)>>>,
+}
+
+impl Blocklist {
+    /// Compiles the stored patterns. Patterns that no longer compile are
+    /// logged and skipped rather than keeping the server from starting.
+    pub fn load(db: &Database) -> Result<Self, Error> {
+        let mut entries = Vec::new();
+        for entry in db.iter_block_rules() {
+            let (pattern, rule) = entry?;
+            match compile(&pattern, rule.kind) {
+                Ok(regex) => entries.push((BlockEntry { pattern, rule }, regex)),
+                Err(e) => warn!("skipping blocklist pattern {pattern:?}: {e}"),
+            }
+        }
+
+        Ok(Self {
+            entries: Arc::new(RwLock::new(entries)),
+        })
+    }
+
+    /// Whether `slug` matches any blocked pattern, ignoring case.
+    pub fn is_blocked(&self, slug: &str) -> bool {
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .any(|(_, regex)| regex.is_match(slug))
+    }
+
+    pub fn entries(&self) -> Vec<BlockEntry> {
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(entry, _)| entry.clone())
+            .collect()
+    }
+
+    /// Blocks slugs matching `pattern`, replacing any entry with the same pattern.
+    /// `regex` is the pattern as returned by [`compile`].
+    pub fn insert(
+        &self,
+        db: &Database,
+        pattern: &str,
+        kind: PatternKind,
+        regex: Regex,
+    ) -> Result<BlockEntry, Error> {
+        let entry = BlockEntry {
+            pattern: pattern.to_string(),
+            rule: BlockRule {
+                kind,
+                created: Utc::now(),
+            },
+        };
+
+        let mut entries = self.entries.write().unwrap();
+        db.insert_block_rule(pattern, &entry.rule)?;
+        entries.retain(|(existing, _)| existing.pattern != pattern);
+        entries.push((entry.clone(), regex));
+
+        Ok(entry)
+    }
+
+    /// Unblocks `pattern`. Returns `false` if it was not blocked.
+    pub fn remove(&self, db: &Database, pattern: &str) -> Result<bool, Error> {
+        let mut entries = self.entries.write().unwrap();
+        let removed = db.remove_block_rule(pattern)?.is_some();
+        entries.retain(|(existing, _)| existing.pattern != pattern);
+
+        Ok(removed)
+    }
+}
+
+/// Turns a pattern into a case-insensitive regex. Exact and glob patterns must
+/// match the whole slug, while regex patterns match anywhere unless anchored.
+pub fn compile(pattern: &str, kind: PatternKind) -> Result<Regex, regex::Error> {
+    let source = match kind {
+        PatternKind::Exact => format!("^{}$", regex::escape(pattern)),
+        PatternKind::Glob => {
+            let mut source = String::from("^");
+            for c in pattern.chars() {
+                match c {
+                    '*' => source.push_str(".*"),
+                    '?' => source.push('.'),
+                    c => source.push_str(&regex::escape(&c.to_string())),
+                }
+            }
+            source.push('$');
+            source
+        }
+        PatternKind::Regex => pattern.to_string(),
+    };
+
+    RegexBuilder::new(&source)
+        .case_insensitive(true)
+        .size_limit(1 << 20)
+        .build()
+}
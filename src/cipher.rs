@@ -0,0 +1,248 @@
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use thiserror::Error;
+
+/// Length of an encryption key in bytes.
+pub const KEY_LEN: usize = 32;
+
+const KEY_ID_LEN: usize = 8;
+const NONCE_LEN: usize = 24;
+
+/// Marks a value stored in the clear.
+const PLAIN: u8 = 0;
+/// Marks a value encrypted with the key whose id follows.
+const SEALED: u8 = 1;
+
+/// Why a stored value could not be opened.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum CipherError {
+    #[error("invalid encryption key: {0}")]
+    Key(String),
+
+    #[error("value is encrypted with unknown key {0}")]
+    UnknownKey(String),
+
+    #[error("value is truncated or has an unknown header")]
+    Malformed,
+
+    #[error("value failed to encrypt")]
+    Encryption,
+
+    #[error("value failed to decrypt")]
+    Decryption,
+}
+
+struct Key {
+    id: [u8; KEY_ID_LEN],
+    aead: XChaCha20Poly1305,
+}
+
+impl Key {
+    fn new(key: &[u8; KEY_LEN]) -> Self {
+        let derived = blake3::derive_key("rentry-rs 2024-04 at-rest key id", key);
+        let mut id = [0; KEY_ID_LEN];
+        id.copy_from_slice(&derived[..KEY_ID_LEN]);
+        Self {
+            id,
+            aead: XChaCha20Poly1305::new(key.into()),
+        }
+    }
+}
+
+/// Encrypts values of the database at rest.
+///
+/// Every value starts with a header byte telling whether it is stored in the
+/// clear or encrypted, so encryption can be turned on for an existing
+/// database. Encrypted values carry the id of their key, which lets records
+/// written under a previous key be read while they are re-encrypted.
+pub struct Cipher {
+    current: Option<Key>,
+    previous: Vec<Key>,
+}
+
+impl std::fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cipher")
+            .field(
+                "current",
+                &self.current.as_ref().map(|key| hex::encode(key.id)),
+            )
+            .field(
+                "previous",
+                &self
+                    .previous
+                    .iter()
+                    .map(|key| hex::encode(key.id))
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Parses a hex encoded key, ignoring surrounding whitespace such as the
+/// newline at the end of a key file.
+pub fn parse_key(key: &str) -> Result<[u8; KEY_LEN], CipherError> {
+    let mut parsed = [0; KEY_LEN];
+    hex::decode_to_slice(key.trim(), &mut parsed)
+        .map_err(|_| CipherError::Key(format!("expected {} hex characters", KEY_LEN * 2)))?;
+    Ok(parsed)
+}
+
+impl Cipher {
+    /// Encrypts new values with `current`, or stores them in the clear when it
+    /// is `None`. Values under any of the `previous` keys can still be read.
+    pub fn new(current: Option<&[u8; KEY_LEN]>, previous: &[[u8; KEY_LEN]]) -> Self {
+        Self {
+            current: current.map(Key::new),
+            previous: previous.iter().map(Key::new).collect(),
+        }
+    }
+
+    /// Encodes `plaintext` for storage. `aad` binds the value to where it is
+    /// stored, so it cannot be moved to another key without being noticed.
+    pub fn seal(&self, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, CipherError> {
+        let Some(ref key) = self.current else {
+            let mut value = Vec::with_capacity(1 + plaintext.len());
+            value.push(PLAIN);
+            value.extend_from_slice(plaintext);
+            return Ok(value);
+        };
+
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let ciphertext = key
+            .aead
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .map_err(|_| CipherError::Encryption)?;
+
+        let mut value = Vec::with_capacity(1 + KEY_ID_LEN + NONCE_LEN + ciphertext.len());
+        value.push(SEALED);
+        value.extend_from_slice(&key.id);
+        value.extend_from_slice(&nonce);
+        value.extend_from_slice(&ciphertext);
+        Ok(value)
+    }
+
+    /// Decodes a value written by [`Cipher::seal`] with the same `aad`.
+    pub fn open(&self, aad: &[u8], value: &[u8]) -> Result<Vec<u8>, CipherError> {
+        match value.split_first() {
+            Some((&PLAIN, plaintext)) => Ok(plaintext.to_vec()),
+            Some((&SEALED, rest)) if rest.len() >= KEY_ID_LEN + NONCE_LEN => {
+                let (id, rest) = rest.split_at(KEY_ID_LEN);
+                let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+                let key = self
+                    .keys()
+                    .find(|key| key.id == id)
+                    .ok_or_else(|| CipherError::UnknownKey(hex::encode(id)))?;
+                key.aead
+                    .decrypt(
+                        XNonce::from_slice(nonce),
+                        Payload {
+                            msg: ciphertext,
+                            aad,
+                        },
+                    )
+                    .map_err(|_| CipherError::Decryption)
+            }
+            _ => Err(CipherError::Malformed),
+        }
+    }
+
+    /// Whether `value` is stored the way [`Cipher::seal`] would store it now,
+    /// that is under the current key, or in the clear if there is none.
+    pub fn is_current(&self, value: &[u8]) -> bool {
+        match (&self.current, value.split_first()) {
+            (None, Some((&PLAIN, _))) => true,
+            (Some(key), Some((&SEALED, rest))) => rest.starts_with(&key.id),
+            _ => false,
+        }
+    }
+
+    /// Whether any key is configured, current or previous.
+    pub fn has_keys(&self) -> bool {
+        self.keys().next().is_some()
+    }
+
+    fn keys(&self) -> impl Iterator<Item = &Key> {
+        self.current.iter().chain(&self.previous)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: [u8; KEY_LEN] = [1; KEY_LEN];
+    const NEW: [u8; KEY_LEN] = [2; KEY_LEN];
+
+    #[test]
+    fn sealed_values_round_trip() {
+        for cipher in [Cipher::new(None, &[]), Cipher::new(Some(&NEW), &[])] {
+            let value = cipher.seal(b"slugs/abc", b"record").unwrap();
+            assert!(cipher.is_current(&value));
+            assert_eq!(cipher.open(b"slugs/abc", &value).unwrap(), b"record");
+        }
+    }
+
+    #[test]
+    fn encrypted_values_hide_the_plaintext() {
+        let cipher = Cipher::new(Some(&NEW), &[]);
+        let first = cipher.seal(b"documents/x", b"secret secret").unwrap();
+        let second = cipher.seal(b"documents/x", b"secret secret").unwrap();
+
+        assert_ne!(first, second);
+        assert!(!first.windows(6).any(|w| w == b"secret"));
+    }
+
+    #[test]
+    fn values_are_bound_to_their_key() {
+        let cipher = Cipher::new(Some(&NEW), &[]);
+        let value = cipher.seal(b"slugs/abc", b"record").unwrap();
+
+        assert_eq!(
+            cipher.open(b"slugs/xyz", &value),
+            Err(CipherError::Decryption)
+        );
+    }
+
+    #[test]
+    fn previous_keys_can_still_open_values() {
+        let old = Cipher::new(Some(&OLD), &[]);
+        let value = old.seal(b"k", b"record").unwrap();
+
+        let rotated = Cipher::new(Some(&NEW), &[OLD]);
+        assert!(!rotated.is_current(&value));
+        assert_eq!(rotated.open(b"k", &value).unwrap(), b"record");
+
+        let forgotten = Cipher::new(Some(&NEW), &[]);
+        assert!(matches!(
+            forgotten.open(b"k", &value),
+            Err(CipherError::UnknownKey(_))
+        ));
+    }
+
+    #[test]
+    fn plaintext_values_are_not_current_once_a_key_is_set() {
+        let value = Cipher::new(None, &[]).seal(b"k", b"record").unwrap();
+        let cipher = Cipher::new(Some(&NEW), &[]);
+
+        assert!(!cipher.is_current(&value));
+        assert_eq!(cipher.open(b"k", &value).unwrap(), b"record");
+        assert_eq!(cipher.open(b"k", &[7, 1, 2]), Err(CipherError::Malformed));
+    }
+
+    #[test]
+    fn keys_are_parsed_from_hex() {
+        let hex = format!("{}\n", hex::encode(NEW));
+        assert_eq!(parse_key(&hex).unwrap(), NEW);
+        assert!(parse_key("abcd").is_err());
+        assert!(parse_key(&"zz".repeat(KEY_LEN)).is_err());
+    }
+}
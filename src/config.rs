@@ -0,0 +1,680 @@
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+
+use crate::cipher::{parse_key, KEY_LEN};
+
+/// Settings for the server, read from a TOML file and then overridden by
+/// environment variables and command line flags, in that order.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Address the HTTP server listens on.
+    pub bind: SocketAddr,
+    pub database: DatabaseConfig,
+    pub limits: Limits,
+    /// Seconds between garbage collection passes.
+    pub gc_interval_secs: u64,
+    /// Seconds between sweeps for expired pastes.
+    pub reap_interval_secs: u64,
+    /// How many bytes of rendered HTML are kept in memory.
+    pub render_cache_bytes: usize,
+    /// Bearer token for the admin API, which is disabled when unset.
+    pub admin_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub backend: DatabaseBackend,
+    /// Directory of the sled database, or the SQLite database file.
+    pub path: PathBuf,
+    /// Size of the page cache in bytes.
+    pub cache_capacity_bytes: u64,
+    /// zstd compression level used by sled.
+    pub compression_factor: i32,
+    /// Hex encoded 32 byte key that slug and document records are encrypted
+    /// with. They are stored in the clear when neither this nor
+    /// `encryption_key_file` is set.
+    pub encryption_key: Option<String>,
+    /// File holding the encryption key, for keeping it out of the config.
+    pub encryption_key_file: Option<PathBuf>,
+    /// Keys records may still be encrypted with. They are only used to read
+    /// those records until they have been re-encrypted with the current key.
+    pub previous_encryption_keys: Vec<String>,
+    pub previous_encryption_key_files: Vec<PathBuf>,
+}
+
+/// Which [`Store`](crate::db::Store) pastes are kept in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseBackend {
+    Sled,
+    Sqlite,
+}
+
+/// The keys named by a [`DatabaseConfig`], read and parsed.
+#[derive(Default)]
+pub struct EncryptionKeys {
+    pub current: Option<[u8; KEY_LEN]>,
+    pub previous: Vec<[u8; KEY_LEN]>,
+}
+
+/// Limits applied to user input, and the lengths of generated slugs and edit codes.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    pub max_document_bytes: usize,
+    pub min_slug_len: usize,
+    pub max_slug_len: usize,
+    pub min_edit_code_len: usize,
+    pub max_edit_code_len: usize,
+    pub max_edit_message_bytes: usize,
+    pub min_view_password_len: usize,
+    pub max_view_password_len: usize,
+    pub generated_slug_len: usize,
+    pub generated_edit_code_len: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            database: DatabaseConfig::default(),
+            limits: Limits::default(),
+            gc_interval_secs: 60 * 60,
+            reap_interval_secs: 60,
+            render_cache_bytes: 64 * 1024 * 1024,
+            admin_token: None,
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            backend: DatabaseBackend::Sled,
+            path: PathBuf::from("./database"),
+            cache_capacity_bytes: 1024 * 1024 * 1024,
+            compression_factor: 10,
+            encryption_key: None,
+            encryption_key_file: None,
+            previous_encryption_keys: Vec::new(),
+            previous_encryption_key_files: Vec::new(),
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_document_bytes: 200_000,
+            min_slug_len: 4,
+            max_slug_len: 32,
+            min_edit_code_len: 4,
+            max_edit_code_len: 32,
+            max_edit_message_bytes: 256,
+            min_view_password_len: 4,
+            max_view_password_len: 128,
+            generated_slug_len: 8,
+            generated_edit_code_len: 16,
+        }
+    }
+}
+
+impl DatabaseConfig {
+    /// Reads the configured encryption keys, from the key files if need be.
+    pub fn encryption_keys(&self) -> Result<EncryptionKeys, String> {
+        fn read(path: &PathBuf) -> Result<String, String> {
+            std::fs::read_to_string(path)
+                .map_err(|e| format!("failed to read {}: {e}", path.display()))
+        }
+        fn parse(key: &str, name: &str) -> Result<[u8; KEY_LEN], String> {
+            parse_key(key).map_err(|e| format!("{name}: {e}"))
+        }
+
+        let current = match (&self.encryption_key, &self.encryption_key_file) {
+            (Some(_), Some(_)) => {
+                return Err("only one of encryption_key and encryption_key_file may be set".into())
+            }
+            (Some(key), None) => Some(parse(key, "encryption_key")?),
+            (None, Some(path)) => Some(parse(&read(path)?, &path.display().to_string())?),
+            (None, None) => None,
+        };
+
+        let mut previous = Vec::new();
+        for key in &self.previous_encryption_keys {
+            previous.push(parse(key, "previous_encryption_keys")?);
+        }
+        for path in &self.previous_encryption_key_files {
+            previous.push(parse(&read(path)?, &path.display().to_string())?);
+        }
+
+        Ok(EncryptionKeys { current, previous })
+    }
+}
+
+impl Config {
+    /// Builds the configuration from the defaults, the optional config file,
+    /// and whatever was passed through the environment or on the command line.
+    pub fn load(cli: &Cli) -> Result<Self, String> {
+        let mut config = match &cli.config {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+                toml::from_str(&contents)
+                    .map_err(|e| format!("failed to parse {}: {e}", path.display()))?
+            }
+            None => Config::default(),
+        };
+
+        cli.overrides.apply(&mut config);
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn gc_interval(&self) -> Duration {
+        Duration::from_secs(self.gc_interval_secs)
+    }
+
+    pub fn reap_interval(&self) -> Duration {
+        Duration::from_secs(self.reap_interval_secs)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let limits = &self.limits;
+
+        if limits.min_slug_len == 0 || limits.min_slug_len > limits.max_slug_len {
+            return Err("slug lengths must satisfy 0 < min_slug_len <= max_slug_len".into());
+        }
+        if limits.min_edit_code_len == 0 || limits.min_edit_code_len > limits.max_edit_code_len {
+            return Err(
+                "edit code lengths must satisfy 0 < min_edit_code_len <= max_edit_code_len".into(),
+            );
+        }
+        if limits.min_view_password_len == 0
+            || limits.min_view_password_len > limits.max_view_password_len
+        {
+            return Err(
+                "view password lengths must satisfy 0 < min_view_password_len <= max_view_password_len"
+                    .into(),
+            );
+        }
+        if !(limits.min_slug_len..=limits.max_slug_len).contains(&limits.generated_slug_len) {
+            return Err("generated_slug_len must be between min_slug_len and max_slug_len".into());
+        }
+        if !(limits.min_edit_code_len..=limits.max_edit_code_len)
+            .contains(&limits.generated_edit_code_len)
+        {
+            return Err(
+                "generated_edit_code_len must be between min_edit_code_len and max_edit_code_len"
+                    .into(),
+            );
+        }
+        if self.gc_interval_secs == 0 || self.reap_interval_secs == 0 {
+            return Err("task intervals must be at least one second".into());
+        }
+        if self
+            .admin_token
+            .as_ref()
+            .is_some_and(|token| token.len() < 16)
+        {
+            return Err("admin_token must be at least 16 characters".into());
+        }
+        let keys = self.database.encryption_keys()?;
+        if self.database.backend != DatabaseBackend::Sled
+            && (keys.current.is_some() || !keys.previous.is_empty())
+        {
+            return Err("encryption at rest is only supported by the sled backend".into());
+        }
+
+        Ok(())
+    }
+}
+
+/// A markdown pastebin.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Path to a TOML configuration file.
+    #[arg(short, long, env = "RENTRY_CONFIG", global = true)]
+    pub config: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub overrides: Overrides,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run a single garbage collection pass and exit. A sled database can only
+    /// be opened by one process, so while the server runs use
+    /// `POST /api/admin/gc` instead.
+    Gc,
+    /// Re-encrypt every record still stored under a previous key, or in the
+    /// clear, with the current encryption key and exit.
+    Reencrypt,
+    /// Hash every edit code still stored in plaintext and exit.
+    HashEditCodes,
+    /// List the pastes set aside because their slug only differed in case
+    /// from an older one, and exit.
+    SlugCollisions,
+}
+
+/// Settings that can be given through the environment or on the command line,
+/// taking precedence over the config file.
+#[derive(Debug, Args)]
+pub struct Overrides {
+    /// Address to listen on.
+    #[arg(long, env = "RENTRY_BIND", global = true)]
+    pub bind: Option<SocketAddr>,
+
+    /// Storage backend for pastes.
+    #[arg(long, env = "RENTRY_DATABASE_BACKEND", global = true)]
+    pub database_backend: Option<DatabaseBackend>,
+
+    /// Directory of the sled database, or the SQLite database file.
+    #[arg(long, env = "RENTRY_DATABASE_PATH", global = true)]
+    pub database_path: Option<PathBuf>,
+
+    /// Size of the page cache in bytes.
+    #[arg(long, env = "RENTRY_DATABASE_CACHE_CAPACITY_BYTES", global = true)]
+    pub database_cache_capacity_bytes: Option<u64>,
+
+    /// zstd compression level used by sled.
+    #[arg(long, env = "RENTRY_DATABASE_COMPRESSION_FACTOR", global = true)]
+    pub database_compression_factor: Option<i32>,
+
+    /// Hex encoded key to encrypt records with.
+    #[arg(
+        long,
+        env = "RENTRY_DATABASE_ENCRYPTION_KEY",
+        global = true,
+        hide_env_values = true
+    )]
+    pub database_encryption_key: Option<String>,
+
+    /// File holding the key to encrypt records with.
+    #[arg(long, env = "RENTRY_DATABASE_ENCRYPTION_KEY_FILE", global = true)]
+    pub database_encryption_key_file: Option<PathBuf>,
+
+    /// Comma separated hex encoded keys records may still be encrypted with.
+    #[arg(
+        long,
+        env = "RENTRY_DATABASE_PREVIOUS_ENCRYPTION_KEYS",
+        global = true,
+        hide_env_values = true,
+        value_delimiter = ','
+    )]
+    pub database_previous_encryption_keys: Option<Vec<String>>,
+
+    /// Comma separated files holding keys records may still be encrypted with.
+    #[arg(
+        long,
+        env = "RENTRY_DATABASE_PREVIOUS_ENCRYPTION_KEY_FILES",
+        global = true,
+        value_delimiter = ','
+    )]
+    pub database_previous_encryption_key_files: Option<Vec<PathBuf>>,
+
+    #[arg(long, env = "RENTRY_MAX_DOCUMENT_BYTES", global = true)]
+    pub max_document_bytes: Option<usize>,
+
+    #[arg(long, env = "RENTRY_MIN_SLUG_LEN", global = true)]
+    pub min_slug_len: Option<usize>,
+
+    #[arg(long, env = "RENTRY_MAX_SLUG_LEN", global = true)]
+    pub max_slug_len: Option<usize>,
+
+    #[arg(long, env = "RENTRY_MIN_EDIT_CODE_LEN", global = true)]
+    pub min_edit_code_len: Option<usize>,
+
+    #[arg(long, env = "RENTRY_MAX_EDIT_CODE_LEN", global = true)]
+    pub max_edit_code_len: Option<usize>,
+
+    #[arg(long, env = "RENTRY_MAX_EDIT_MESSAGE_BYTES", global = true)]
+    pub max_edit_message_bytes: Option<usize>,
+
+    #[arg(long, env = "RENTRY_MIN_VIEW_PASSWORD_LEN", global = true)]
+    pub min_view_password_len: Option<usize>,
+
+    #[arg(long, env = "RENTRY_MAX_VIEW_PASSWORD_LEN", global = true)]
+    pub max_view_password_len: Option<usize>,
+
+    /// Length of slugs generated when no custom slug is given.
+    #[arg(long, env = "RENTRY_GENERATED_SLUG_LEN", global = true)]
+    pub generated_slug_len: Option<usize>,
+
+    /// Length of edit codes generated when none is given.
+    #[arg(long, env = "RENTRY_GENERATED_EDIT_CODE_LEN", global = true)]
+    pub generated_edit_code_len: Option<usize>,
+
+    /// Seconds between garbage collection passes.
+    #[arg(long, env = "RENTRY_GC_INTERVAL_SECS", global = true)]
+    pub gc_interval_secs: Option<u64>,
+
+    /// Seconds between sweeps for expired pastes.
+    #[arg(long, env = "RENTRY_REAP_INTERVAL_SECS", global = true)]
+    pub reap_interval_secs: Option<u64>,
+
+    /// Bytes of rendered HTML kept in memory.
+    #[arg(long, env = "RENTRY_RENDER_CACHE_BYTES", global = true)]
+    pub render_cache_bytes: Option<usize>,
+
+    /// Bearer token for the admin API.
+    #[arg(
+        long,
+        env = "RENTRY_ADMIN_TOKEN",
+        global = true,
+        hide_env_values = true
+    )]
+    pub admin_token: Option<String>,
+}
+
+impl Overrides {
+    fn apply(&self, config: &mut Config) {
+        fn set<T: Clone>(target: &mut T, value: &Option<T>) {
+            if let Some(value) = value {
+                *target = value.clone();
+            }
+        }
+
+        set(&mut config.bind, &self.bind);
+        set(&mut config.database.backend, &self.database_backend);
+        set(&mut config.database.path, &self.database_path);
+        set(
+            &mut config.database.cache_capacity_bytes,
+            &self.database_cache_capacity_bytes,
+        );
+        set(
+            &mut config.database.compression_factor,
+            &self.database_compression_factor,
+        );
+        // a key given here replaces a key file from the config and vice versa
+        if self.database_encryption_key.is_some() || self.database_encryption_key_file.is_some() {
+            config.database.encryption_key = self.database_encryption_key.clone();
+            config.database.encryption_key_file = self.database_encryption_key_file.clone();
+        }
+        set(
+            &mut config.database.previous_encryption_keys,
+            &self.database_previous_encryption_keys,
+        );
+        set(
+            &mut config.database.previous_encryption_key_files,
+            &self.database_previous_encryption_key_files,
+        );
+        set(
+            &mut config.limits.max_document_bytes,
+            &self.max_document_bytes,
+        );
+        set(&mut config.limits.min_slug_len, &self.min_slug_len);
+        set(&mut config.limits.max_slug_len, &self.max_slug_len);
+        set(
+            &mut config.limits.min_edit_code_len,
+            &self.min_edit_code_len,
+        );
+        set(
+            &mut config.limits.max_edit_code_len,
+            &self.max_edit_code_len,
+        );
+        set(
+            &mut config.limits.max_edit_message_bytes,
+            &self.max_edit_message_bytes,
+        );
+        set(
+            &mut config.limits.min_view_password_len,
+            &self.min_view_password_len,
+        );
+        set(
+            &mut config.limits.max_view_password_len,
+            &self.max_view_password_len,
+        );
+        set(
+            &mut config.limits.generated_slug_len,
+            &self.generated_slug_len,
+        );
+        set(
+            &mut config.limits.generated_edit_code_len,
+            &self.generated_edit_code_len,
+        );
+        set(&mut config.gc_interval_secs, &self.gc_interval_secs);
+        set(&mut config.reap_interval_secs, &self.reap_interval_secs);
+        set(&mut config.render_cache_bytes, &self.render_cache_bytes);
+        if let Some(ref token) = self.admin_token {
+            config.admin_token = Some(token.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    const KEY: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+    /// Held while the environment is changed and read, as tests run on many threads.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    /// Parses `args` as the command line, with `env` set as the environment.
+    fn cli(args: &[&str], env: &[(&str, &str)]) -> Cli {
+        let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        for (name, value) in env {
+            std::env::set_var(name, value);
+        }
+        let cli = Cli::try_parse_from([&["rentry-rs"], args].concat());
+        for (name, _) in env {
+            std::env::remove_var(name);
+        }
+        cli.unwrap()
+    }
+
+    /// Loads the config from a file holding `toml`, then `args` and `env`.
+    fn load(toml: &str, args: &[&str], env: &[(&str, &str)]) -> Result<Config, String> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rentry.toml");
+        std::fs::write(&path, toml).unwrap();
+
+        let path = path.to_str().unwrap();
+        Config::load(&cli(&[&["--config", path], args].concat(), env))
+    }
+
+    #[test]
+    fn command_lines_are_parsed() {
+        let cli = cli(
+            &[
+                "--bind",
+                "127.0.0.1:8080",
+                "--database-backend",
+                "sqlite",
+                "--database-previous-encryption-keys",
+                "aa,bb",
+                "gc",
+            ],
+            &[],
+        );
+
+        assert!(cli.config.is_none());
+        assert!(matches!(cli.command, Some(Command::Gc)));
+        assert_eq!(cli.overrides.bind, Some(([127, 0, 0, 1], 8080).into()));
+        assert_eq!(
+            cli.overrides.database_backend,
+            Some(DatabaseBackend::Sqlite)
+        );
+        assert_eq!(
+            cli.overrides.database_previous_encryption_keys,
+            Some(vec!["aa".to_string(), "bb".to_string()])
+        );
+        assert!(Cli::try_parse_from(["rentry-rs", "--bind", "nowhere"]).is_err());
+    }
+
+    #[test]
+    fn config_files_are_loaded_over_the_defaults() {
+        let config = load(
+            r#"
+                bind = "127.0.0.1:4000"
+                admin_token = "a-long-enough-admin-token"
+
+                [database]
+                backend = "sqlite"
+                path = "/var/lib/rentry/pastes.db"
+
+                [limits]
+                max_slug_len = 16
+            "#,
+            &[],
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(config.bind, ([127, 0, 0, 1], 4000).into());
+        assert_eq!(
+            config.admin_token.as_deref(),
+            Some("a-long-enough-admin-token")
+        );
+        assert_eq!(config.database.backend, DatabaseBackend::Sqlite);
+        assert_eq!(
+            config.database.path,
+            PathBuf::from("/var/lib/rentry/pastes.db")
+        );
+        assert_eq!(config.limits.max_slug_len, 16);
+        // and whatever the file leaves out keeps its default
+        assert_eq!(config.limits.min_slug_len, Limits::default().min_slug_len);
+        assert_eq!(config.gc_interval(), Duration::from_secs(60 * 60));
+
+        let unknown = load("colour = \"blue\"", &[], &[]).unwrap_err();
+        assert!(unknown.contains("failed to parse"), "{unknown}");
+        let invalid = load("[limits]\nmin_slug_len = 0", &[], &[]).unwrap_err();
+        assert!(invalid.contains("min_slug_len"), "{invalid}");
+        let missing = Config::load(&cli(&["--config", "/nonexistent/rentry.toml"], &[]));
+        assert!(missing.unwrap_err().contains("failed to read"));
+    }
+
+    #[test]
+    fn the_environment_overrides_the_file_and_flags_override_the_environment() {
+        let file = "bind = \"127.0.0.1:4000\"\ngc_interval_secs = 10\nreap_interval_secs = 10";
+        let env = [
+            ("RENTRY_BIND", "127.0.0.1:5000"),
+            ("RENTRY_GC_INTERVAL_SECS", "20"),
+        ];
+
+        let from_env = load(file, &[], &env).unwrap();
+        assert_eq!(from_env.bind, ([127, 0, 0, 1], 5000).into());
+        assert_eq!(from_env.gc_interval_secs, 20);
+        assert_eq!(from_env.reap_interval_secs, 10);
+
+        let from_flags = load(file, &["--bind", "127.0.0.1:6000"], &env).unwrap();
+        assert_eq!(from_flags.bind, ([127, 0, 0, 1], 6000).into());
+        assert_eq!(from_flags.gc_interval_secs, 20);
+    }
+
+    #[test]
+    fn encryption_keys_and_key_files_replace_each_other() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_file = dir.path().join("database.key");
+        std::fs::write(&key_file, format!("{KEY}\n")).unwrap();
+        let key_file = key_file.to_str().unwrap();
+
+        let from_file = format!("[database]\nencryption_key_file = {key_file:?}");
+        let config = load(&from_file, &["--database-encryption-key", KEY], &[]).unwrap();
+        assert_eq!(config.database.encryption_key.as_deref(), Some(KEY));
+        assert_eq!(config.database.encryption_key_file, None);
+
+        let from_key = format!("[database]\nencryption_key = {KEY:?}");
+        let env = [("RENTRY_DATABASE_ENCRYPTION_KEY_FILE", key_file)];
+        let config = load(&from_key, &[], &env).unwrap();
+        assert_eq!(config.database.encryption_key, None);
+        assert_eq!(
+            config.database.encryption_key_file,
+            Some(PathBuf::from(key_file))
+        );
+        let keys = config.database.encryption_keys().unwrap();
+        assert_eq!(keys.current, Some([1; KEY_LEN]));
+
+        // only a file that sets both is contradictory
+        let both = format!("{from_key}\nencryption_key_file = {key_file:?}");
+        let error = load(&both, &[], &[]).unwrap_err();
+        assert!(error.contains("only one of"), "{error}");
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        type Change = fn(&mut Config);
+        let cases: &[(Change, &str)] = &[
+            (|c| c.limits.min_slug_len = 0, "min_slug_len"),
+            (
+                |c| c.limits.min_slug_len = c.limits.max_slug_len + 1,
+                "min_slug_len",
+            ),
+            (|c| c.limits.min_edit_code_len = 0, "min_edit_code_len"),
+            (
+                |c| c.limits.max_edit_code_len = c.limits.min_edit_code_len - 1,
+                "min_edit_code_len",
+            ),
+            (
+                |c| c.limits.min_view_password_len = 0,
+                "min_view_password_len",
+            ),
+            (
+                |c| c.limits.max_view_password_len = c.limits.min_view_password_len - 1,
+                "min_view_password_len",
+            ),
+            (
+                |c| c.limits.generated_slug_len = c.limits.max_slug_len + 1,
+                "generated_slug_len",
+            ),
+            (
+                |c| c.limits.generated_edit_code_len = c.limits.min_edit_code_len - 1,
+                "generated_edit_code_len",
+            ),
+            (|c| c.gc_interval_secs = 0, "task intervals"),
+            (|c| c.reap_interval_secs = 0, "task intervals"),
+            (|c| c.admin_token = Some("too-short".into()), "admin_token"),
+            (
+                |c| c.database.encryption_key = Some("not hex".into()),
+                "encryption_key",
+            ),
+            (
+                |c| c.database.previous_encryption_keys = vec!["not hex".into()],
+                "previous_encryption_keys",
+            ),
+            (
+                |c| c.database.encryption_key_file = Some("/nonexistent/database.key".into()),
+                "failed to read",
+            ),
+            (
+                |c| {
+                    c.database.encryption_key = Some(KEY.into());
+                    c.database.encryption_key_file = Some("/nonexistent/database.key".into());
+                },
+                "only one of",
+            ),
+            (
+                |c| {
+                    c.database.backend = DatabaseBackend::Sqlite;
+                    c.database.encryption_key = Some(KEY.into());
+                },
+                "sled backend",
+            ),
+            (
+                |c| {
+                    c.database.backend = DatabaseBackend::Sqlite;
+                    c.database.previous_encryption_keys = vec![KEY.into()];
+                },
+                "sled backend",
+            ),
+        ];
+
+        assert_eq!(Config::default().validate(), Ok(()));
+        for (change, expected) in cases {
+            let mut config = Config::default();
+            change(&mut config);
+            let error = config.validate().unwrap_err();
+            assert!(error.contains(expected), "{expected}: {error}");
+        }
+    }
+}
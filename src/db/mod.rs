@@ -0,0 +1,272 @@
+use std::{ops::Deref, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{DatabaseBackend, DatabaseConfig},
+    errors::Error,
+};
+
+pub use sled_store::SledStore;
+pub use sqlite_store::SqliteStore;
+
+mod sled_store;
+mod sqlite_store;
+
+/// A handle to the [`Store`] picked by the config, cheap to clone.
+#[derive(Debug, Clone)]
+pub struct Database(Arc<dyn Store>);
+
+impl Database {
+    pub fn new(config: &DatabaseConfig) -> Result<Self, Error> {
+        let store: Arc<dyn Store> = match config.backend {
+            DatabaseBackend::Sled => Arc::new(SledStore::open(config)?),
+            DatabaseBackend::Sqlite => Arc::new(SqliteStore::open(config)?),
+        };
+        Ok(Self(store))
+    }
+}
+
+impl Deref for Database {
+    type Target = dyn Store;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+/// An iterator over the records of a [`Store`].
+pub type Iter<'a, T> = Box<dyn Iterator<Item = Result<T, Error>> + 'a>;
+
+/// Where pastes, their documents and everything around them are kept.
+///
+/// Slugs may be passed in any casing, as stores key them by [`normalize_slug`].
+/// Operations touching several kinds of records at once, such as creating or
+/// renaming a paste, run in a single transaction, and the `_if_unchanged`
+/// operations only write if the record still matches what the caller read.
+pub trait Store: std::fmt::Debug + Send + Sync {
+    fn insert_document(&self, doc: &DocumentRecord) -> Result<DocumentHash, Error>;
+
+    fn get_document(&self, hash: &DocumentHash) -> Result<Option<DocumentRecord>, Error>;
+
+    /// Removes a document only if it still matches `expected`, so a document
+    /// that was re-inserted in the meantime is left alone.
+    fn remove_document_if_unchanged(
+        &self,
+        hash: &DocumentHash,
+        expected: &DocumentRecord,
+    ) -> Result<bool, Error>;
+
+    fn iter_documents(&self) -> Iter<'_, (DocumentHash, DocumentRecord)>;
+
+    fn get_slug(&self, slug: &str) -> Result<Option<SlugRecord>, Error>;
+
+    fn remove_slug(&self, slug: &str) -> Result<Option<SlugRecord>, Error>;
+
+    /// Removes a slug only if its record still matches `expected`, so a slug
+    /// that was edited or re-created in the meantime is left alone.
+    fn remove_slug_if_unchanged(&self, slug: &str, expected: &SlugRecord) -> Result<bool, Error>;
+
+    /// Replaces the record of a slug only if it still matches `expected`.
+    fn replace_slug_if_unchanged(
+        &self,
+        slug: &str,
+        expected: &SlugRecord,
+        record: &SlugRecord,
+    ) -> Result<bool, Error>;
+
+    /// Counts one read of a burn-after-reading slug, deleting it on its last read.
+    ///
+    /// The decrement only happens if the record still matches `expected`, so
+    /// when two readers race for the same view only one of them gets `true`
+    /// and the other has to look the slug up again.
+    fn consume_slug_read(&self, slug: &str, expected: &SlugRecord) -> Result<bool, Error>;
+
+    fn iter_slugs(&self) -> Iter<'_, (String, SlugRecord)>;
+
+    /// Creates a new paste, with its document, first revision and expiry entry.
+    ///
+    /// The slug is only claimed if it is free or held by an expired paste, so
+    /// two concurrent requests for the same slug can never both succeed.
+    /// Returns `false` without writing anything if the slug is taken.
+    fn insert_paste(
+        &self,
+        slug: &str,
+        record: &SlugRecord,
+        doc: &DocumentRecord,
+        revision: &RevisionRecord,
+    ) -> Result<bool, Error>;
+
+    /// Moves a paste to a new slug, leaving its old slug behind as an alias.
+    /// Changing only the casing of the slug just updates how it is displayed.
+    ///
+    /// The paste must still match `expected`, and the new slug must be free,
+    /// held by an expired paste or one of the paste's own aliases.
+    fn move_slug(
+        &self,
+        slug: &str,
+        new_slug: &str,
+        expected: &SlugRecord,
+    ) -> Result<MoveOutcome, Error>;
+
+    /// Looks up the paste an old slug was renamed to.
+    fn resolve_alias(&self, slug: &str) -> Result<Option<SlugRecord>, Error>;
+
+    /// Replaces the whole revision history of `slug`.
+    fn insert_revisions(
+        &self,
+        slug: &str,
+        revisions: &[RevisionRecord],
+    ) -> Result<Option<Vec<RevisionRecord>>, Error>;
+
+    /// Points a paste at the document of an edit, appending `revision` to its
+    /// history and adding the expiry entry of `record`, all at once.
+    ///
+    /// Nothing is written unless the paste still matches `expected`, in which
+    /// case `false` is returned.
+    fn commit_edit(
+        &self,
+        slug: &str,
+        expected: &SlugRecord,
+        record: &SlugRecord,
+        revision: &RevisionRecord,
+    ) -> Result<bool, Error>;
+
+    /// Returns the revision history of `slug`, oldest first.
+    fn get_revisions(&self, slug: &str) -> Result<Vec<RevisionRecord>, Error>;
+
+    fn remove_revisions(&self, slug: &str) -> Result<Option<Vec<RevisionRecord>>, Error>;
+
+    fn iter_revisions(&self) -> Iter<'_, (String, Vec<RevisionRecord>)>;
+
+    fn remove_expiry(&self, at: DateTime<Utc>, slug: &str) -> Result<(), Error>;
+
+    /// Iterates over the expiry entries that are due before `now`, oldest first.
+    ///
+    /// Entries are not removed when a paste is edited or deleted, so the slug
+    /// record is the source of truth and an entry may be stale.
+    fn iter_expired(&self, now: DateTime<Utc>) -> Iter<'_, (DateTime<Utc>, String)>;
+
+    fn insert_block_rule(
+        &self,
+        pattern: &str,
+        rule: &BlockRule,
+    ) -> Result<Option<BlockRule>, Error>;
+
+    fn remove_block_rule(&self, pattern: &str) -> Result<Option<BlockRule>, Error>;
+
+    fn iter_block_rules(&self) -> Iter<'_, (String, BlockRule)>;
+
+    /// Iterates over the pastes set aside because their slug only differed in
+    /// case from an older one.
+    fn iter_slug_collisions(&self) -> Iter<'_, (String, SlugCollision)>;
+
+    /// Re-encrypts every record not stored under the current encryption key
+    /// and returns how many were rewritten. Stores that do not encrypt their
+    /// records have nothing to do.
+    fn reencrypt(&self) -> Result<usize, Error> {
+        Ok(0)
+    }
+}
+
+/// Returns the key a slug is stored under. Slugs are case-insensitive, so
+/// `MyNotes` and `mynotes` are the same paste.
+pub fn normalize_slug(slug: &str) -> String {
+    slug.to_ascii_lowercase()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DocumentHash([u8; 32]);
+
+impl AsRef<[u8; 32]> for DocumentHash {
+    fn as_ref(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl DocumentRecord {
+    /// Returns the content address of this document.
+    pub fn hash(&self) -> DocumentHash {
+        DocumentHash(*blake3::hash(self.content.as_bytes()).as_bytes())
+    }
+}
+
+impl std::fmt::Display for DocumentHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl std::str::FromStr for DocumentHash {
+    type Err = hex::FromHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut hash = [0; 32];
+        hex::decode_to_slice(s, &mut hash)?;
+        Ok(DocumentHash(hash))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentRecord {
+    pub content: String,
+    pub created: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlugRecord {
+    pub document_hash: DocumentHash,
+    pub edit_code: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub reads_remaining: Option<u32>,  // burn after this many reads
+    pub display_slug: String,          // the slug with the casing it was created with
+    pub aliases: Vec<String>,          // old slugs that still lead here, normalized
+    pub view_password: Option<String>, // argon2 hash of the password needed to read the paste
+    pub encrypted: bool,               // content is an envelope only the client can decrypt
+}
+
+/// The result of [`Store::move_slug`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum MoveOutcome {
+    Moved(SlugRecord),
+    /// The new slug is held by another paste or alias.
+    Taken,
+    /// The paste no longer matches the expected record.
+    Changed,
+}
+
+impl SlugRecord {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|at| at <= Utc::now())
+    }
+}
+
+/// How a blocklist pattern is matched against slugs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatternKind {
+    Exact,
+    Glob,
+    Regex,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockRule {
+    pub kind: PatternKind,
+    pub created: DateTime<Utc>,
+}
+
+/// A paste set aside by the migration to case-insensitive slugs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlugCollision<R = SlugRecord> {
+    pub record: R,
+    pub revisions: Vec<RevisionRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RevisionRecord {
+    pub document_hash: DocumentHash,
+    pub created: DateTime<Utc>,
+    pub message: Option<String>,
+}
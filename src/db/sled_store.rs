@@ -0,0 +1,1204 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sled::{
+    transaction::{ConflictableTransactionError, TransactionalTree},
+    IVec, Transactional,
+};
+
+use super::{
+    normalize_slug, BlockRule, DocumentHash, DocumentRecord, Iter, MoveOutcome, RevisionRecord,
+    SlugCollision, SlugRecord, Store,
+};
+use crate::{
+    cipher::{Cipher, CipherError},
+    config::DatabaseConfig,
+    errors::Error,
+};
+
+/// The layout version of the records stored in the database.
+///
+/// Records are bincode encoded, which is not self-describing, so any change to
+/// a record's fields must bump this and add a step to [`SledStore::migrate`].
+const SCHEMA_VERSION: u32 = 7;
+
+/// A [`Store`] keeping each kind of record in its own sled tree, with values
+/// encoded by bincode.
+#[derive(Debug, Clone)]
+pub struct SledStore {
+    db: sled::Db, // stores database metadata such as the schema version
+
+    slugs: sled::Tree,      // stores all urls
+    documents: sled::Tree,  // stores all docs
+    revisions: sled::Tree,  // stores the revision history of every url
+    expiry: sled::Tree,     // stores expiring urls ordered by expiry time
+    blocklist: sled::Tree,  // stores patterns of slugs that may not be registered
+    collisions: sled::Tree, // stores pastes whose slug only differed in case from an older one
+    aliases: sled::Tree,    // maps old slugs of renamed pastes to their current slug
+
+    cipher: Arc<Cipher>, // encrypts the values of `slugs` and `documents` at rest
+}
+
+impl SledStore {
+    pub fn open(config: &DatabaseConfig) -> Result<Self, Error> {
+        let keys = config.encryption_keys().map_err(CipherError::Key)?;
+        let cipher = Cipher::new(keys.current.as_ref(), &keys.previous);
+
+        let db = sled::Config::default()
+            .use_compression(true)
+            // .mode(sled::Mode::HighThroughput)
+            .compression_factor(config.compression_factor)
+            .cache_capacity(config.cache_capacity_bytes)
+            .path(&config.path)
+            .open()?;
+
+        Self::with_db(db, cipher)
+    }
+
+    /// Opens the trees of an opened sled database and brings them up to date.
+    fn with_db(db: sled::Db, cipher: Cipher) -> Result<Self, Error> {
+        let slugs = db.open_tree("slugs")?;
+        let documents = db.open_tree("documents")?;
+        let revisions = db.open_tree("revisions")?;
+        let expiry = db.open_tree("expiry")?;
+        let blocklist = db.open_tree("blocklist")?;
+        let collisions = db.open_tree("slug_collisions")?;
+        let aliases = db.open_tree("aliases")?;
+
+        let database = Self {
+            db,
+            slugs,
+            documents,
+            revisions,
+            expiry,
+            blocklist,
+            collisions,
+            aliases,
+            cipher: Arc::new(cipher),
+        };
+        database.migrate()?;
+
+        Ok(database)
+    }
+
+    /// Brings records written by older versions up to [`SCHEMA_VERSION`].
+    fn migrate(&self) -> Result<(), Error> {
+        let stored: Option<u32> = Self::get_and_transform(&self.db, "schema_version")?;
+        // every step is a no-op on a new database, but one whose pastes were
+        // all deleted may still hold documents in the old layout
+        let mut version = stored.unwrap_or(0);
+
+        if version == 0 {
+            Self::migrate_tree(&self.slugs, |old: legacy::SlugRecordV0| {
+                legacy::SlugRecordV1 {
+                    document_hash: old.document_hash,
+                    edit_code: old.edit_code,
+                    expires_at: None,
+                }
+            })?;
+            version = 1;
+        }
+
+        if version == 1 {
+            Self::migrate_tree(&self.slugs, |old: legacy::SlugRecordV1| {
+                legacy::SlugRecordV2 {
+                    document_hash: old.document_hash,
+                    edit_code: old.edit_code,
+                    expires_at: old.expires_at,
+                    reads_remaining: None,
+                }
+            })?;
+            version = 2;
+        }
+
+        if version == 2 {
+            self.normalize_slug_keys()?;
+            version = 3;
+        }
+
+        if version == 3 {
+            self.migrate_slug_records(|old: legacy::SlugRecordV3| legacy::SlugRecordV4 {
+                document_hash: old.document_hash,
+                edit_code: old.edit_code,
+                expires_at: old.expires_at,
+                reads_remaining: old.reads_remaining,
+                display_slug: old.display_slug,
+                aliases: Vec::new(),
+            })?;
+            version = 4;
+        }
+
+        if version == 4 {
+            self.migrate_slug_records(|old: legacy::SlugRecordV4| legacy::SlugRecordV5 {
+                document_hash: old.document_hash,
+                edit_code: old.edit_code,
+                expires_at: old.expires_at,
+                reads_remaining: old.reads_remaining,
+                display_slug: old.display_slug,
+                aliases: old.aliases,
+                view_password: None,
+            })?;
+            version = 5;
+        }
+
+        if version == 5 {
+            self.migrate_slug_records(|old: legacy::SlugRecordV5| SlugRecord {
+                document_hash: old.document_hash,
+                edit_code: old.edit_code,
+                expires_at: old.expires_at,
+                reads_remaining: old.reads_remaining,
+                display_slug: old.display_slug,
+                aliases: old.aliases,
+                view_password: old.view_password,
+                encrypted: false,
+            })?;
+            version = 6;
+        }
+
+        if version == 6 {
+            self.seal_tree(&self.slugs)?;
+            self.seal_tree(&self.documents)?;
+            version = 7;
+        }
+
+        debug_assert_eq!(version, SCHEMA_VERSION, "a migration step is missing");
+        Self::insert_and_transform::<_, _, u32>(&self.db, "schema_version", version)?;
+        Ok(())
+    }
+
+    /// Rewrites every record of a slug-keyed tree from one layout into the next.
+    fn migrate_tree<Old, New>(tree: &sled::Tree, upgrade: impl Fn(Old) -> New) -> Result<(), Error>
+    where
+        Old: FromIVec,
+        New: IntoIVec,
+    {
+        for entry in Self::iter::<String, Old>(tree) {
+            let (slug, record) = entry?;
+            tree.insert(slug.to_ivec()?, upgrade(record).to_ivec()?)?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites every slug record from one layout into the next, including
+    /// those of pastes set aside in the `slug_collisions` tree.
+    fn migrate_slug_records<Old, New>(&self, upgrade: impl Fn(Old) -> New) -> Result<(), Error>
+    where
+        Old: for<'de> Deserialize<'de>,
+        New: Serialize,
+    {
+        Self::migrate_tree(&self.slugs, &upgrade)?;
+        Self::migrate_tree(&self.collisions, |old: SlugCollision<Old>| SlugCollision {
+            record: upgrade(old.record),
+            revisions: old.revisions,
+        })
+    }
+
+    /// Adds the header of [`Cipher::seal`] to every value of a tree written
+    /// before records could be encrypted, encrypting them if a key is set.
+    fn seal_tree(&self, tree: &sled::Tree) -> Result<(), Error> {
+        for entry in tree.iter() {
+            let (key, value) = entry?;
+            let sealed = self.cipher.seal(&Self::aad(tree, &key), &value)?;
+            tree.insert(key, sealed)?;
+        }
+        Ok(())
+    }
+
+    /// Moves every slug, its revisions and its expiry entries to the
+    /// lowercase key, keeping the original casing for display.
+    ///
+    /// Slugs that only differ in case used to be separate pastes. The one
+    /// created first keeps the slug, and the others are moved out of the way
+    /// into the `slug_collisions` tree and logged, so an admin can sort them out.
+    fn normalize_slug_keys(&self) -> Result<(), Error> {
+        let mut groups: BTreeMap<String, Vec<(String, legacy::SlugRecordV2)>> = BTreeMap::new();
+        for entry in Self::iter::<String, legacy::SlugRecordV2>(&self.slugs) {
+            let (slug, record) = entry?;
+            groups
+                .entry(normalize_slug(&slug))
+                .or_default()
+                .push((slug, record));
+        }
+
+        for (key, group) in groups {
+            let mut pastes = Vec::with_capacity(group.len());
+            for (slug, old) in group {
+                let revisions: Vec<RevisionRecord> =
+                    Self::get_and_transform(&self.revisions, slug.as_str())?.unwrap_or_default();
+                let created = match revisions.first() {
+                    Some(first) => Some(first.created),
+                    None => Self::get_and_transform::<_, DocumentRecord>(
+                        &self.documents,
+                        old.document_hash,
+                    )?
+                    .map(|doc| doc.created),
+                };
+
+                self.slugs.remove(slug.as_str().to_ivec()?)?;
+                self.revisions.remove(slug.as_str().to_ivec()?)?;
+
+                let record = legacy::SlugRecordV3 {
+                    document_hash: old.document_hash,
+                    edit_code: old.edit_code,
+                    expires_at: old.expires_at,
+                    reads_remaining: old.reads_remaining,
+                    display_slug: slug.clone(),
+                };
+                pastes.push((created, slug, record, revisions));
+            }
+
+            // oldest first, with pastes of unknown age last
+            pastes.sort_by_key(|(created, slug, ..)| (created.is_none(), *created, slug.clone()));
+            let mut pastes = pastes.into_iter();
+
+            if let Some((_, _, record, revisions)) = pastes.next() {
+                Self::insert_and_transform::<_, _, legacy::SlugRecordV3>(
+                    &self.slugs,
+                    key.as_str(),
+                    &record,
+                )?;
+                if !revisions.is_empty() {
+                    self.insert_revisions(&key, &revisions)?;
+                }
+            }
+            for (_, slug, record, revisions) in pastes {
+                warn!("slug {slug:?} collides with an older paste under {key:?}, moved to slug_collisions");
+                Self::insert_and_transform::<_, _, SlugCollision<legacy::SlugRecordV3>>(
+                    &self.collisions,
+                    slug.as_str(),
+                    &SlugCollision { record, revisions },
+                )?;
+            }
+        }
+
+        for entry in self.expiry.iter() {
+            let (k, v) = entry?;
+            let key = ExpiryKey::from_ivec(&k)?;
+            let normalized = ExpiryKey::new(key.at(), &key.slug);
+            if normalized != key {
+                self.expiry.remove(k)?;
+                self.expiry.insert(normalized.to_ivec()?, v)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether `alias` still leads somewhere. Aliases are not removed along
+    /// with their paste, so they only count while the paste they point to
+    /// is alive and still lists them.
+    fn is_live_alias(
+        &self,
+        slugs: &TransactionalTree,
+        alias: &AliasRecord,
+        alias_key: &IVec,
+    ) -> Result<bool, ConflictableTransactionError<Error>> {
+        let target_key = alias
+            .slug
+            .to_ivec()
+            .map_err(|e| ConflictableTransactionError::Abort(e.into()))?;
+        let Some(target) = slugs.get(&target_key)? else {
+            return Ok(false);
+        };
+        let target: SlugRecord = self.open_in_transaction(&self.slugs, &target_key, &target)?;
+        let alias_key = Self::decode_in_transaction::<String>(alias_key)?;
+
+        Ok(!target.is_expired() && target.aliases.contains(&alias_key))
+    }
+
+    fn decode_in_transaction<V: FromIVec>(
+        ivec: &IVec,
+    ) -> Result<V, ConflictableTransactionError<Error>> {
+        V::from_ivec(ivec).map_err(|e| ConflictableTransactionError::Abort(e.into()))
+    }
+
+    fn open_in_transaction<V: FromIVec>(
+        &self,
+        tree: &sled::Tree,
+        key: &IVec,
+        value: &IVec,
+    ) -> Result<V, ConflictableTransactionError<Error>> {
+        self.unseal(tree, key, value)
+            .map_err(ConflictableTransactionError::Abort)
+    }
+
+    /// What a sealed value is bound to: the tree and key it is stored under.
+    fn aad(tree: &sled::Tree, key: &[u8]) -> Vec<u8> {
+        [&tree.name()[..], b"/", key].concat()
+    }
+
+    fn seal<V: IntoIVec>(&self, tree: &sled::Tree, key: &[u8], value: &V) -> Result<IVec, Error> {
+        let sealed = self.cipher.seal(&Self::aad(tree, key), &value.to_ivec()?)?;
+        Ok(sealed.into())
+    }
+
+    fn unseal<V: FromIVec>(&self, tree: &sled::Tree, key: &[u8], value: &[u8]) -> Result<V, Error> {
+        let plaintext = self.cipher.open(&Self::aad(tree, key), value)?;
+        Ok(V::from_ivec(&plaintext.into())?)
+    }
+
+    fn iter_sealed<'a, K, V>(
+        &'a self,
+        tree: &'a sled::Tree,
+    ) -> impl Iterator<Item = Result<(K, V), Error>> + 'a
+    where
+        K: FromIVec,
+        V: FromIVec,
+    {
+        tree.iter().map(move |result| {
+            let (k, v) = result?;
+            let value = self.unseal(tree, &k, &v)?;
+            Ok((K::from_ivec(&k)?, value))
+        })
+    }
+
+    fn insert_sealed<K, V, T>(
+        &self,
+        tree: &sled::Tree,
+        key: K,
+        value: V,
+    ) -> Result<Option<T>, Error>
+    where
+        K: IntoIVec,
+        V: IntoIVec,
+        T: FromIVec,
+    {
+        let key = key.to_ivec()?;
+        let value = self.seal(tree, &key, &value)?;
+        let previous = tree.insert(&key, value)?;
+        previous.map(|p| self.unseal(tree, &key, &p)).transpose()
+    }
+
+    fn get_sealed<K, V>(&self, tree: &sled::Tree, key: K) -> Result<Option<V>, Error>
+    where
+        K: IntoIVec,
+        V: FromIVec,
+    {
+        let key = key.to_ivec()?;
+        let value = tree.get(&key)?;
+        value.map(|v| self.unseal(tree, &key, &v)).transpose()
+    }
+
+    fn remove_sealed<K, V>(&self, tree: &sled::Tree, key: K) -> Result<Option<V>, Error>
+    where
+        K: IntoIVec,
+        V: FromIVec,
+    {
+        let key = key.to_ivec()?;
+        let value = tree.remove(&key)?;
+        value.map(|v| self.unseal(tree, &key, &v)).transpose()
+    }
+
+    /// Replaces a sealed value with `new`, or removes it if `new` is `None`,
+    /// only if it still decodes to `expected`.
+    ///
+    /// Sealing the same record twice gives different bytes, so the stored
+    /// value is compared by what it decodes to and the swap is retried when
+    /// it was only re-encrypted in the meantime.
+    fn compare_and_swap_sealed<K, V>(
+        &self,
+        tree: &sled::Tree,
+        key: K,
+        expected: &V,
+        new: Option<&V>,
+    ) -> Result<bool, Error>
+    where
+        K: IntoIVec,
+        V: IntoIVec + FromIVec + PartialEq,
+    {
+        let key = key.to_ivec()?;
+        let new = new.map(|v| self.seal(tree, &key, v)).transpose()?;
+
+        loop {
+            let Some(current) = tree.get(&key)? else {
+                return Ok(false);
+            };
+            if self.unseal::<V>(tree, &key, &current)? != *expected {
+                return Ok(false);
+            }
+            if tree
+                .compare_and_swap(&key, Some(current), new.clone())?
+                .is_ok()
+            {
+                return Ok(true);
+            }
+        }
+    }
+
+    fn iter<K, V>(store: &sled::Tree) -> impl Iterator<Item = Result<(K, V), Error>>
+    where
+        K: FromIVec,
+        V: FromIVec,
+    {
+        store.iter().map(|result| match result {
+            Ok((k, v)) => {
+                let k = K::from_ivec(&k)?;
+                let v = V::from_ivec(&v)?;
+                Ok::<_, Error>((k, v))
+            }
+            Err(e) => Err(e.into()),
+        })
+    }
+
+    fn insert_and_transform<K, V, T>(
+        store: &sled::Tree,
+        key: K,
+        value: V,
+    ) -> Result<Option<T>, Error>
+    where
+        K: IntoIVec,
+        V: IntoIVec,
+        T: FromIVec,
+    {
+        let previous = store.insert(key.to_ivec()?, value.to_ivec()?)?;
+        Ok(previous.map(|p| T::from_ivec(&p)).transpose()?)
+    }
+
+    fn get_and_transform<K, V>(store: &sled::Tree, key: K) -> Result<Option<V>, Error>
+    where
+        K: IntoIVec,
+        V: FromIVec,
+    {
+        let value = store.get(key.to_ivec()?)?;
+        Ok(value.map(|p| FromIVec::from_ivec(&p)).transpose()?)
+    }
+
+    fn remove<K, V>(store: &sled::Tree, key: K) -> Result<Option<V>, Error>
+    where
+        K: IntoIVec,
+        V: FromIVec,
+    {
+        Ok(store
+            .remove(key.to_ivec()?)?
+            .map(|p| FromIVec::from_ivec(&p))
+            .transpose()?)
+    }
+}
+
+impl Store for SledStore {
+    fn insert_document(&self, doc: &DocumentRecord) -> Result<DocumentHash, Error> {
+        let hash = doc.hash();
+        self.insert_sealed::<_, _, DocumentRecord>(&self.documents, &hash, doc)?;
+        Ok(hash)
+    }
+
+    // fn insert_document(&self, hash: &DocumentHash, doc: &DocumentRecord) -> Result<Option<DocumentRecord>, Error> {
+    //     Self::insert_and_transform(&self.documents, hash, doc)
+    // }
+
+    fn get_document(&self, hash: &DocumentHash) -> Result<Option<DocumentRecord>, Error> {
+        self.get_sealed(&self.documents, hash)
+    }
+
+    fn remove_document_if_unchanged(
+        &self,
+        hash: &DocumentHash,
+        expected: &DocumentRecord,
+    ) -> Result<bool, Error> {
+        self.compare_and_swap_sealed(&self.documents, hash, expected, None)
+    }
+
+    fn iter_documents(&self) -> Iter<'_, (DocumentHash, DocumentRecord)> {
+        Box::new(self.iter_sealed(&self.documents))
+    }
+
+    fn get_slug(&self, slug: &str) -> Result<Option<SlugRecord>, Error> {
+        self.get_sealed(&self.slugs, normalize_slug(slug))
+    }
+
+    fn remove_slug(&self, slug: &str) -> Result<Option<SlugRecord>, Error> {
+        self.remove_sealed(&self.slugs, normalize_slug(slug))
+    }
+
+    fn remove_slug_if_unchanged(&self, slug: &str, expected: &SlugRecord) -> Result<bool, Error> {
+        self.compare_and_swap_sealed(&self.slugs, normalize_slug(slug), expected, None)
+    }
+
+    fn replace_slug_if_unchanged(
+        &self,
+        slug: &str,
+        expected: &SlugRecord,
+        record: &SlugRecord,
+    ) -> Result<bool, Error> {
+        self.compare_and_swap_sealed(&self.slugs, normalize_slug(slug), expected, Some(record))
+    }
+
+    fn consume_slug_read(&self, slug: &str, expected: &SlugRecord) -> Result<bool, Error> {
+        let Some(reads_remaining) = expected.reads_remaining else {
+            return Ok(true);
+        };
+
+        let next = match reads_remaining {
+            0 | 1 => None,
+            n => Some(SlugRecord {
+                reads_remaining: Some(n - 1),
+                ..expected.clone()
+            }),
+        };
+
+        self.compare_and_swap_sealed(&self.slugs, normalize_slug(slug), expected, next.as_ref())
+    }
+
+    fn iter_slugs(&self) -> Iter<'_, (String, SlugRecord)> {
+        Box::new(self.iter_sealed(&self.slugs))
+    }
+
+    fn insert_paste(
+        &self,
+        slug: &str,
+        record: &SlugRecord,
+        doc: &DocumentRecord,
+        revision: &RevisionRecord,
+    ) -> Result<bool, Error> {
+        let slug_key = normalize_slug(slug).to_ivec()?;
+        let slug_value = self.seal(&self.slugs, &slug_key, record)?;
+        let doc_key = record.document_hash.to_ivec()?;
+        let doc_value = self.seal(&self.documents, &doc_key, doc)?;
+        let revisions_value = vec![revision].to_ivec()?;
+        let expiry_key = record
+            .expires_at
+            .map(|at| ExpiryKey::new(at, slug).to_ivec())
+            .transpose()?;
+        let expiry_value = ().to_ivec()?;
+
+        let trees = (
+            &self.slugs,
+            &self.documents,
+            &self.revisions,
+            &self.expiry,
+            &self.aliases,
+        );
+        let created = trees.transaction(|(slugs, documents, revisions, expiry, aliases)| {
+            if let Some(existing) = slugs.get(&slug_key)? {
+                let existing: SlugRecord =
+                    self.open_in_transaction(&self.slugs, &slug_key, &existing)?;
+                if !existing.is_expired() {
+                    return Ok(false);
+                }
+            }
+            if let Some(alias) = aliases.get(&slug_key)? {
+                let alias = Self::decode_in_transaction::<AliasRecord>(&alias)?;
+                if self.is_live_alias(slugs, &alias, &slug_key)? {
+                    return Ok(false);
+                }
+                aliases.remove(&slug_key)?;
+            }
+
+            slugs.insert(&slug_key, &slug_value)?;
+            documents.insert(&doc_key, &doc_value)?;
+            revisions.insert(&slug_key, &revisions_value)?;
+            if let Some(ref expiry_key) = expiry_key {
+                expiry.insert(expiry_key, &expiry_value)?;
+            }
+
+            Ok(true)
+        })?;
+
+        Ok(created)
+    }
+
+    fn move_slug(
+        &self,
+        slug: &str,
+        new_slug: &str,
+        expected: &SlugRecord,
+    ) -> Result<MoveOutcome, Error> {
+        let old_key = normalize_slug(slug);
+        let new_key = normalize_slug(new_slug);
+
+        let mut record = SlugRecord {
+            display_slug: new_slug.to_string(),
+            ..expected.clone()
+        };
+        if new_key != old_key {
+            record.aliases.retain(|alias| *alias != new_key);
+            record.aliases.push(old_key.clone());
+        }
+
+        let old_slug_key = old_key.to_ivec()?;
+        let new_slug_key = new_key.to_ivec()?;
+        let record_value = self.seal(&self.slugs, &new_slug_key, &record)?;
+        let alias_keys = record
+            .aliases
+            .iter()
+            .map(|alias| alias.to_ivec())
+            .collect::<Result<Vec<_>, _>>()?;
+        let alias_value = AliasRecord {
+            slug: new_key.clone(),
+        }
+        .to_ivec()?;
+        let expiry_keys = match expected.expires_at {
+            Some(at) => Some((
+                ExpiryKey::new(at, &old_key).to_ivec()?,
+                ExpiryKey::new(at, &new_key).to_ivec()?,
+            )),
+            None => None,
+        };
+        let expiry_value = ().to_ivec()?;
+
+        let trees = (&self.slugs, &self.revisions, &self.expiry, &self.aliases);
+        let outcome = trees.transaction(|(slugs, revisions, expiry, aliases)| {
+            let Some(current) = slugs.get(&old_slug_key)? else {
+                return Ok(MoveOutcome::Changed);
+            };
+            let current: SlugRecord =
+                self.open_in_transaction(&self.slugs, &old_slug_key, &current)?;
+            if current != *expected {
+                return Ok(MoveOutcome::Changed);
+            }
+
+            if new_key != old_key {
+                if let Some(existing) = slugs.get(&new_slug_key)? {
+                    let existing: SlugRecord =
+                        self.open_in_transaction(&self.slugs, &new_slug_key, &existing)?;
+                    if !existing.is_expired() {
+                        return Ok(MoveOutcome::Taken);
+                    }
+                }
+                if let Some(alias) = aliases.get(&new_slug_key)? {
+                    let alias = Self::decode_in_transaction::<AliasRecord>(&alias)?;
+                    if alias.slug != old_key && self.is_live_alias(slugs, &alias, &new_slug_key)? {
+                        return Ok(MoveOutcome::Taken);
+                    }
+                    aliases.remove(&new_slug_key)?;
+                }
+
+                slugs.remove(&old_slug_key)?;
+                match revisions.remove(&old_slug_key)? {
+                    Some(history) => revisions.insert(&new_slug_key, history)?,
+                    // don't inherit the history of an expired paste that held the new slug
+                    None => revisions.remove(&new_slug_key)?,
+                };
+                if let Some((ref old_expiry, ref new_expiry)) = expiry_keys {
+                    expiry.remove(old_expiry)?;
+                    expiry.insert(new_expiry, &expiry_value)?;
+                }
+                for alias_key in &alias_keys {
+                    aliases.insert(alias_key, &alias_value)?;
+                }
+            }
+
+            slugs.insert(&new_slug_key, &record_value)?;
+            Ok(MoveOutcome::Moved(record.clone()))
+        })?;
+
+        Ok(outcome)
+    }
+
+    fn resolve_alias(&self, slug: &str) -> Result<Option<SlugRecord>, Error> {
+        let key = normalize_slug(slug);
+        let alias: Option<AliasRecord> = Self::get_and_transform(&self.aliases, key.as_str())?;
+        let Some(alias) = alias else {
+            return Ok(None);
+        };
+
+        let record = self.get_slug(&alias.slug)?;
+        Ok(record.filter(|record| record.aliases.contains(&key)))
+    }
+
+    fn insert_revisions(
+        &self,
+        slug: &str,
+        revisions: &[RevisionRecord],
+    ) -> Result<Option<Vec<RevisionRecord>>, Error> {
+        Self::insert_and_transform(&self.revisions, normalize_slug(slug), revisions)
+    }
+
+    fn commit_edit(
+        &self,
+        slug: &str,
+        expected: &SlugRecord,
+        record: &SlugRecord,
+        revision: &RevisionRecord,
+    ) -> Result<bool, Error> {
+        let slug_key = normalize_slug(slug).to_ivec()?;
+        let slug_value = self.seal(&self.slugs, &slug_key, record)?;
+        let expiry_key = record
+            .expires_at
+            .map(|at| ExpiryKey::new(at, slug).to_ivec())
+            .transpose()?;
+        let expiry_value = ().to_ivec()?;
+
+        let trees = (&self.slugs, &self.revisions, &self.expiry);
+        let committed = trees.transaction(|(slugs, revisions, expiry)| {
+            let Some(current) = slugs.get(&slug_key)? else {
+                return Ok(false);
+            };
+            let current: SlugRecord = self.open_in_transaction(&self.slugs, &slug_key, &current)?;
+            if current != *expected {
+                return Ok(false);
+            }
+
+            let mut history = match revisions.get(&slug_key)? {
+                Some(history) => Self::decode_in_transaction::<Vec<RevisionRecord>>(&history)?,
+                None => Vec::new(),
+            };
+            history.push(revision.clone());
+            let history = history
+                .to_ivec()
+                .map_err(|e| ConflictableTransactionError::Abort(e.into()))?;
+
+            slugs.insert(&slug_key, &slug_value)?;
+            revisions.insert(&slug_key, history)?;
+            if let Some(ref expiry_key) = expiry_key {
+                expiry.insert(expiry_key, &expiry_value)?;
+            }
+
+            Ok(true)
+        })?;
+
+        Ok(committed)
+    }
+
+    fn get_revisions(&self, slug: &str) -> Result<Vec<RevisionRecord>, Error> {
+        let history: Option<Vec<RevisionRecord>> =
+            Self::get_and_transform(&self.revisions, normalize_slug(slug))?;
+        Ok(history.unwrap_or_default())
+    }
+
+    fn remove_revisions(&self, slug: &str) -> Result<Option<Vec<RevisionRecord>>, Error> {
+        Self::remove(&self.revisions, normalize_slug(slug))
+    }
+
+    fn iter_revisions(&self) -> Iter<'_, (String, Vec<RevisionRecord>)> {
+        Box::new(Self::iter(&self.revisions))
+    }
+
+    fn remove_expiry(&self, at: DateTime<Utc>, slug: &str) -> Result<(), Error> {
+        Self::remove::<_, ()>(&self.expiry, ExpiryKey::new(at, slug))?;
+        Ok(())
+    }
+
+    fn insert_block_rule(
+        &self,
+        pattern: &str,
+        rule: &BlockRule,
+    ) -> Result<Option<BlockRule>, Error> {
+        Self::insert_and_transform(&self.blocklist, pattern, rule)
+    }
+
+    fn remove_block_rule(&self, pattern: &str) -> Result<Option<BlockRule>, Error> {
+        Self::remove(&self.blocklist, pattern)
+    }
+
+    fn iter_slug_collisions(&self) -> Iter<'_, (String, SlugCollision)> {
+        Box::new(Self::iter(&self.collisions))
+    }
+
+    fn iter_block_rules(&self) -> Iter<'_, (String, BlockRule)> {
+        Box::new(Self::iter(&self.blocklist))
+    }
+
+    fn iter_expired(&self, now: DateTime<Utc>) -> Iter<'_, (DateTime<Utc>, String)> {
+        let upper = ExpiryKey::new(now, "").to_ivec();
+        let range = upper.map(|upper| self.expiry.range(..upper));
+
+        Box::new(range.into_iter().flat_map(|range| {
+            range.map(|result| {
+                let (k, _) = result?;
+                let key = ExpiryKey::from_ivec(&k)?;
+                Ok((key.at(), key.slug))
+            })
+        }))
+    }
+
+    fn reencrypt(&self) -> Result<usize, Error> {
+        if !self.cipher.has_keys() {
+            return Ok(0);
+        }
+
+        let mut rewritten = 0;
+        for tree in [&self.slugs, &self.documents] {
+            for entry in tree.iter() {
+                let (key, value) = entry?;
+                if self.cipher.is_current(&value) {
+                    continue;
+                }
+
+                let aad = Self::aad(tree, &key);
+                let sealed = self.cipher.seal(&aad, &self.cipher.open(&aad, &value)?)?;
+                if tree
+                    .compare_and_swap(key, Some(value), Some(sealed))?
+                    .is_ok()
+                {
+                    rewritten += 1;
+                }
+            }
+        }
+
+        self.db.flush()?;
+        Ok(rewritten)
+    }
+}
+
+pub trait IntoIVec: Sized {
+    fn to_ivec(&self) -> Result<IVec, bincode::Error>;
+}
+
+pub trait FromIVec: Sized {
+    fn from_ivec(ivec: &IVec) -> Result<Self, bincode::Error>;
+}
+
+impl<T> IntoIVec for T
+where
+    T: Serialize,
+{
+    fn to_ivec(&self) -> Result<IVec, bincode::Error> {
+        bincode::serialize(self).map(Into::into)
+    }
+}
+
+impl<T> FromIVec for T
+where
+    T: for<'de> Deserialize<'de>,
+{
+    fn from_ivec(ivec: &IVec) -> Result<Self, bincode::Error> {
+        bincode::deserialize(ivec)
+    }
+}
+
+/// An old slug of a renamed paste, pointing at its current slug.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AliasRecord {
+    pub slug: String,
+}
+
+/// Key of the `expiry` tree.
+///
+/// The timestamp is stored as big-endian milliseconds and bincode writes fixed
+/// size arrays without a length prefix, so keys sort by expiry time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ExpiryKey {
+    at: [u8; 8],
+    slug: String,
+}
+
+impl ExpiryKey {
+    fn new(at: DateTime<Utc>, slug: &str) -> Self {
+        let millis = at.timestamp_millis().max(0) as u64;
+        Self {
+            at: millis.to_be_bytes(),
+            slug: normalize_slug(slug),
+        }
+    }
+
+    fn at(&self) -> DateTime<Utc> {
+        let millis = u64::from_be_bytes(self.at) as i64;
+        DateTime::from_timestamp_millis(millis).unwrap_or_default()
+    }
+}
+
+/// Record layouts written by older schema versions, kept for [`SledStore::migrate`].
+mod legacy {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Serialize};
+
+    use super::DocumentHash;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SlugRecordV0 {
+        pub document_hash: DocumentHash,
+        pub edit_code: String,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SlugRecordV1 {
+        pub document_hash: DocumentHash,
+        pub edit_code: String,
+        pub expires_at: Option<DateTime<Utc>>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SlugRecordV2 {
+        pub document_hash: DocumentHash,
+        pub edit_code: String,
+        pub expires_at: Option<DateTime<Utc>>,
+        pub reads_remaining: Option<u32>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SlugRecordV3 {
+        pub document_hash: DocumentHash,
+        pub edit_code: String,
+        pub expires_at: Option<DateTime<Utc>>,
+        pub reads_remaining: Option<u32>,
+        pub display_slug: String,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SlugRecordV4 {
+        pub document_hash: DocumentHash,
+        pub edit_code: String,
+        pub expires_at: Option<DateTime<Utc>>,
+        pub reads_remaining: Option<u32>,
+        pub display_slug: String,
+        pub aliases: Vec<String>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SlugRecordV5 {
+        pub document_hash: DocumentHash,
+        pub edit_code: String,
+        pub expires_at: Option<DateTime<Utc>>,
+        pub reads_remaining: Option<u32>,
+        pub display_slug: String,
+        pub aliases: Vec<String>,
+        pub view_password: Option<String>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn open(path: &std::path::Path, key: Option<u8>, previous: &[u8]) -> Result<SledStore, Error> {
+        SledStore::open(&DatabaseConfig {
+            path: path.to_path_buf(),
+            encryption_key: key.map(|k| hex::encode([k; 32])),
+            previous_encryption_keys: previous.iter().map(|k| hex::encode([*k; 32])).collect(),
+            ..DatabaseConfig::default()
+        })
+    }
+
+    fn insert(db: &SledStore, slug: &str, content: &str) -> SlugRecord {
+        let doc = DocumentRecord {
+            content: content.to_string(),
+            created: Utc::now(),
+        };
+        let record = SlugRecord {
+            document_hash: doc.hash(),
+            edit_code: "hash".to_string(),
+            expires_at: None,
+            reads_remaining: Some(3),
+            display_slug: slug.to_string(),
+            aliases: Vec::new(),
+            view_password: None,
+            encrypted: false,
+        };
+        let revision = RevisionRecord {
+            document_hash: doc.hash(),
+            created: doc.created,
+            message: None,
+        };
+        assert!(db.insert_paste(slug, &record, &doc, &revision).unwrap());
+        record
+    }
+
+    /// Writes a record the way the baseline did, before records were sealed.
+    fn insert_raw<K: IntoIVec, V: IntoIVec>(db: &sled::Db, tree: &str, key: K, value: &V) {
+        let tree = db.open_tree(tree).unwrap();
+        tree.insert(key.to_ivec().unwrap(), value.to_ivec().unwrap())
+            .unwrap();
+    }
+
+    fn document(content: &str) -> DocumentRecord {
+        DocumentRecord {
+            content: content.to_string(),
+            created: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn unversioned_databases_are_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let raw = sled::open(dir.path()).unwrap();
+        let kept = document("kept");
+        insert_raw(
+            &raw,
+            "slugs",
+            "Notes",
+            &legacy::SlugRecordV0 {
+                document_hash: kept.hash(),
+                edit_code: "code".to_string(),
+            },
+        );
+        insert_raw(&raw, "documents", kept.hash(), &kept);
+        // left behind by a paste deleted before revisions were tracked
+        let orphan = document("orphan");
+        insert_raw(&raw, "documents", orphan.hash(), &orphan);
+
+        let db = SledStore::with_db(raw, Cipher::new(Some(&[1; 32]), &[])).unwrap();
+        let record = db.get_slug("notes").unwrap().unwrap();
+        assert_eq!(record.display_slug, "Notes");
+        assert_eq!(record.edit_code, "code");
+        assert_eq!(db.get_document(&kept.hash()).unwrap(), Some(kept));
+        assert_eq!(db.iter_documents().map(Result::unwrap).count(), 2);
+        assert!(!stored_in_clear(&db, b"orphan"));
+        let version: Option<u32> = SledStore::get_and_transform(&db.db, "schema_version").unwrap();
+        assert_eq!(version, Some(SCHEMA_VERSION));
+    }
+
+    #[test]
+    fn databases_without_slugs_are_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let raw = sled::open(dir.path()).unwrap();
+        let orphan = document("orphan");
+        insert_raw(&raw, "documents", orphan.hash(), &orphan);
+
+        let db = SledStore::with_db(raw, Cipher::new(None, &[])).unwrap();
+        assert_eq!(db.get_document(&orphan.hash()).unwrap(), Some(orphan));
+        assert_eq!(db.iter_documents().map(Result::unwrap).count(), 1);
+    }
+
+    #[test]
+    fn colliding_slugs_are_set_aside_when_normalized() {
+        let dir = tempfile::tempdir().unwrap();
+        let raw = sled::open(dir.path()).unwrap();
+        raw.insert("schema_version".to_ivec().unwrap(), 2u32.to_ivec().unwrap())
+            .unwrap();
+
+        let expires_at = Utc::now() + Duration::days(1);
+        // the oldest casing keeps the slug, going by its first revision or else its document
+        let pastes = [
+            ("NOTES", Some(Duration::days(1))),
+            ("Notes", Some(Duration::days(2))),
+            ("notes", None),
+            ("Other", Some(Duration::days(3))),
+        ];
+        for (slug, age) in pastes {
+            let doc = document(slug);
+            insert_raw(&raw, "documents", doc.hash(), &doc);
+            let record = legacy::SlugRecordV2 {
+                document_hash: doc.hash(),
+                edit_code: format!("code of {slug}"),
+                expires_at: Some(expires_at),
+                reads_remaining: None,
+            };
+            insert_raw(&raw, "slugs", slug, &record);
+            if let Some(age) = age {
+                let revision = RevisionRecord {
+                    document_hash: doc.hash(),
+                    created: Utc::now() - age,
+                    message: Some(slug.to_string()),
+                };
+                insert_raw(&raw, "revisions", slug, &vec![revision]);
+            }
+            let expiry = ExpiryKey {
+                at: ExpiryKey::new(expires_at, slug).at,
+                slug: slug.to_string(),
+            };
+            insert_raw(&raw, "expiry", expiry, &());
+        }
+
+        let db = SledStore::with_db(raw, Cipher::new(None, &[])).unwrap();
+
+        let kept = db.get_slug("NOTES").unwrap().unwrap();
+        assert_eq!(kept.display_slug, "Notes");
+        assert_eq!(kept.edit_code, "code of Notes");
+        let revisions = db.get_revisions("notes").unwrap();
+        assert_eq!(revisions.len(), 1);
+        assert_eq!(revisions[0].message.as_deref(), Some("Notes"));
+        assert_eq!(db.get_slug("other").unwrap().unwrap().display_slug, "Other");
+        assert_eq!(db.get_revisions("OTHER").unwrap().len(), 1);
+
+        let slugs = db
+            .iter_slugs()
+            .map(|entry| entry.unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(slugs, ["notes", "other"]);
+        let revisions = db
+            .iter_revisions()
+            .map(|entry| entry.unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(revisions, ["notes", "other"]);
+
+        let collisions = db
+            .iter_slug_collisions()
+            .collect::<Result<BTreeMap<_, _>, _>>()
+            .unwrap();
+        assert_eq!(collisions.len(), 2);
+        let newer = &collisions["NOTES"];
+        assert_eq!(newer.record.display_slug, "NOTES");
+        assert_eq!(newer.record.edit_code, "code of NOTES");
+        assert_eq!(newer.revisions.len(), 1);
+        assert_eq!(newer.revisions[0].message.as_deref(), Some("NOTES"));
+        // a paste without revisions is dated by its document, which is newest
+        let unknown = &collisions["notes"];
+        assert_eq!(unknown.record.edit_code, "code of notes");
+        assert!(unknown.revisions.is_empty());
+
+        let mut expiring = db
+            .iter_expired(expires_at + Duration::seconds(1))
+            .map(|entry| entry.unwrap().1)
+            .collect::<Vec<_>>();
+        expiring.sort();
+        expiring.dedup();
+        assert_eq!(expiring, ["notes", "other"]);
+    }
+
+    fn stored_in_clear(db: &SledStore, needle: &[u8]) -> bool {
+        [&db.slugs, &db.documents].iter().any(|tree| {
+            tree.iter()
+                .map(Result::unwrap)
+                .any(|(_, v)| v.windows(needle.len()).any(|w| w == needle))
+        })
+    }
+
+    #[test]
+    fn records_are_encrypted_at_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = open(dir.path(), Some(1), &[]).unwrap();
+        let record = insert(&db, "Secret", "top secret content");
+
+        assert!(!stored_in_clear(&db, b"top secret"));
+        assert!(!stored_in_clear(&db, b"Secret"));
+        assert_eq!(db.get_slug("secret").unwrap(), Some(record.clone()));
+        let doc = db.get_document(&record.document_hash).unwrap().unwrap();
+        assert_eq!(doc.content, "top secret content");
+
+        // compare-and-swap looks past the random nonces
+        assert!(db.consume_slug_read("secret", &record).unwrap());
+        assert_eq!(
+            db.get_slug("secret").unwrap().unwrap().reads_remaining,
+            Some(2)
+        );
+        assert!(!db.consume_slug_read("secret", &record).unwrap());
+    }
+
+    /// The same database opened with other keys. sled holds on to its lock
+    /// for a while after being dropped, so it cannot simply be reopened.
+    fn with_keys(db: &SledStore, key: Option<u8>, previous: &[u8]) -> SledStore {
+        let previous: Vec<_> = previous.iter().map(|k| [*k; 32]).collect();
+        SledStore {
+            cipher: Arc::new(Cipher::new(key.map(|k| [k; 32]).as_ref(), &previous)),
+            ..db.clone()
+        }
+    }
+
+    #[test]
+    fn keys_are_rotated_by_reencrypting() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = open(dir.path(), None, &[]).unwrap();
+        let record = insert(&db, "notes", "plain content");
+        assert!(stored_in_clear(&db, b"plain content"));
+        assert_eq!(db.reencrypt().unwrap(), 0);
+
+        let db = with_keys(&db, Some(1), &[]);
+        assert_eq!(db.reencrypt().unwrap(), 2);
+        assert!(!stored_in_clear(&db, b"plain content"));
+
+        let db = with_keys(&db, Some(2), &[1]);
+        assert_eq!(db.get_slug("notes").unwrap(), Some(record.clone()));
+        assert_eq!(db.reencrypt().unwrap(), 2);
+        assert_eq!(db.reencrypt().unwrap(), 0);
+
+        let db = with_keys(&db, Some(2), &[]);
+        assert_eq!(db.get_slug("notes").unwrap(), Some(record.clone()));
+
+        let db = with_keys(&db, Some(3), &[]);
+        assert!(matches!(
+            db.get_slug("notes"),
+            Err(Error::Cipher(CipherError::UnknownKey(_)))
+        ));
+
+        // dropping the key decrypts everything again
+        let db = with_keys(&db, None, &[2]);
+        assert_eq!(db.reencrypt().unwrap(), 2);
+        assert!(stored_in_clear(&db, b"plain content"));
+        assert_eq!(db.get_slug("notes").unwrap(), Some(record));
+    }
+}
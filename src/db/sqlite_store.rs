@@ -0,0 +1,724 @@
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use rusqlite::{
+    params,
+    types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef},
+    Connection, OptionalExtension, Row, TransactionBehavior,
+};
+
+use super::{
+    normalize_slug, BlockRule, DocumentHash, DocumentRecord, Iter, MoveOutcome, PatternKind,
+    RevisionRecord, SlugCollision, SlugRecord, Store,
+};
+use crate::{config::DatabaseConfig, errors::Error};
+
+/// Statements bringing the schema from one version to the next. The version
+/// a database is at is kept in its `user_version` pragma.
+const MIGRATIONS: &[&str] = &[r#"
+    CREATE TABLE slugs (
+        slug            TEXT PRIMARY KEY, -- normalized, see normalize_slug
+        display_slug    TEXT NOT NULL,
+        document_hash   TEXT NOT NULL,
+        edit_code       TEXT NOT NULL,    -- argon2 hash
+        expires_at      TEXT,
+        reads_remaining INTEGER,
+        aliases         TEXT NOT NULL,    -- comma separated old slugs that still lead here
+        view_password   TEXT,             -- argon2 hash
+        encrypted       INTEGER NOT NULL
+    ) WITHOUT ROWID;
+
+    CREATE TABLE documents (
+        hash    TEXT PRIMARY KEY, -- hex encoded blake3 hash of the content
+        content TEXT NOT NULL,
+        created TEXT NOT NULL
+    ) WITHOUT ROWID;
+
+    CREATE TABLE revisions (
+        slug          TEXT NOT NULL,
+        number        INTEGER NOT NULL, -- 1-based
+        document_hash TEXT NOT NULL,
+        created       TEXT NOT NULL,
+        message       TEXT,
+        PRIMARY KEY (slug, number)
+    ) WITHOUT ROWID;
+
+    CREATE TABLE expiry (
+        at   INTEGER NOT NULL, -- unix milliseconds
+        slug TEXT NOT NULL,
+        PRIMARY KEY (at, slug)
+    ) WITHOUT ROWID;
+
+    CREATE TABLE aliases (
+        alias TEXT PRIMARY KEY,
+        slug  TEXT NOT NULL
+    ) WITHOUT ROWID;
+
+    CREATE TABLE blocklist (
+        pattern TEXT PRIMARY KEY,
+        kind    TEXT NOT NULL,
+        created TEXT NOT NULL
+    ) WITHOUT ROWID;
+"#];
+
+/// How many rows iterators fetch at a time.
+const PAGE_SIZE: usize = 256;
+
+/// A [`Store`] keeping records in plain tables of a single SQLite file, so
+/// the database can be backed up and inspected with the usual SQLite tools.
+#[derive(Debug)]
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(config: &DatabaseConfig) -> Result<Self, Error> {
+        let conn = Connection::open(&config.path)?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.pragma_update(
+            None,
+            "cache_size",
+            -((config.cache_capacity_bytes / 1024) as i64),
+        )?;
+        // leave room for someone poking at the file with the sqlite3 shell
+        conn.busy_timeout(Duration::from_secs(5))?;
+
+        let store = Self {
+            conn: Mutex::new(conn),
+        };
+        store.migrate()?;
+
+        Ok(store)
+    }
+
+    fn migrate(&self) -> Result<(), Error> {
+        self.transaction(|conn| {
+            let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+            for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+                conn.execute_batch(migration)?;
+                conn.pragma_update(None, "user_version", i as i64 + 1)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        // every write runs in a transaction that is rolled back on a panic
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Runs `f` in a transaction that takes the write lock right away, and
+    /// commits it unless `f` fails.
+    fn transaction<T>(&self, f: impl FnOnce(&Connection) -> Result<T, Error>) -> Result<T, Error> {
+        let mut conn = self.lock();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let value = f(&tx)?;
+        tx.commit()?;
+        Ok(value)
+    }
+
+    /// Iterates over pages of rows, so neither the lock nor a whole table is
+    /// held while the caller works through them. `fetch` returns the rows
+    /// following the key of the last row of the previous page, if any.
+    fn paged<'a, K, V>(
+        &'a self,
+        fetch: impl Fn(&Connection, Option<&K>) -> rusqlite::Result<Vec<(K, V)>> + 'a,
+    ) -> Iter<'a, (K, V)>
+    where
+        K: Clone + 'a,
+        V: 'a,
+    {
+        let mut page = VecDeque::new();
+        let mut after = None;
+        let mut done = false;
+
+        Box::new(std::iter::from_fn(move || {
+            if page.is_empty() && !done {
+                match fetch(&self.lock(), after.as_ref()) {
+                    Ok(rows) => {
+                        done = rows.len() < PAGE_SIZE;
+                        page.extend(rows);
+                    }
+                    Err(e) => {
+                        done = true;
+                        return Some(Err(e.into()));
+                    }
+                }
+            }
+
+            let (key, value) = page.pop_front()?;
+            after = Some(key.clone());
+            Some(Ok((key, value)))
+        }))
+    }
+
+    /// Replaces the record of a slug with `new`, or removes it if `new` is
+    /// `None`, only if it still matches `expected`.
+    fn swap_slug_if_unchanged(
+        &self,
+        slug: &str,
+        expected: &SlugRecord,
+        new: Option<&SlugRecord>,
+    ) -> Result<bool, Error> {
+        let key = normalize_slug(slug);
+        self.transaction(|conn| {
+            if get_slug(conn, &key)?.as_ref() != Some(expected) {
+                return Ok(false);
+            }
+            match new {
+                Some(record) => put_slug(conn, &key, record)?,
+                None => delete_slug(conn, &key)?,
+            }
+            Ok(true)
+        })
+    }
+}
+
+impl Store for SqliteStore {
+    fn insert_document(&self, doc: &DocumentRecord) -> Result<DocumentHash, Error> {
+        let hash = doc.hash();
+        put_document(&self.lock(), &hash, doc)?;
+        Ok(hash)
+    }
+
+    fn get_document(&self, hash: &DocumentHash) -> Result<Option<DocumentRecord>, Error> {
+        get_document(&self.lock(), hash)
+    }
+
+    fn remove_document_if_unchanged(
+        &self,
+        hash: &DocumentHash,
+        expected: &DocumentRecord,
+    ) -> Result<bool, Error> {
+        self.transaction(|conn| {
+            if get_document(conn, hash)?.as_ref() != Some(expected) {
+                return Ok(false);
+            }
+            conn.execute("DELETE FROM documents WHERE hash = ?1", [hash])?;
+            Ok(true)
+        })
+    }
+
+    fn iter_documents(&self) -> Iter<'_, (DocumentHash, DocumentRecord)> {
+        self.paged(|conn, after: Option<&DocumentHash>| {
+            conn.prepare_cached(
+                "SELECT hash, content, created FROM documents
+                 WHERE ?1 IS NULL OR hash > ?1 ORDER BY hash LIMIT ?2",
+            )?
+            .query_map(params![after, PAGE_SIZE], |row| {
+                Ok((row.get("hash")?, document_record(row)?))
+            })?
+            .collect()
+        })
+    }
+
+    fn get_slug(&self, slug: &str) -> Result<Option<SlugRecord>, Error> {
+        get_slug(&self.lock(), &normalize_slug(slug))
+    }
+
+    fn remove_slug(&self, slug: &str) -> Result<Option<SlugRecord>, Error> {
+        let key = normalize_slug(slug);
+        self.transaction(|conn| {
+            let record = get_slug(conn, &key)?;
+            delete_slug(conn, &key)?;
+            Ok(record)
+        })
+    }
+
+    fn remove_slug_if_unchanged(&self, slug: &str, expected: &SlugRecord) -> Result<bool, Error> {
+        self.swap_slug_if_unchanged(slug, expected, None)
+    }
+
+    fn replace_slug_if_unchanged(
+        &self,
+        slug: &str,
+        expected: &SlugRecord,
+        record: &SlugRecord,
+    ) -> Result<bool, Error> {
+        self.swap_slug_if_unchanged(slug, expected, Some(record))
+    }
+
+    fn consume_slug_read(&self, slug: &str, expected: &SlugRecord) -> Result<bool, Error> {
+        let Some(reads_remaining) = expected.reads_remaining else {
+            return Ok(true);
+        };
+
+        let next = match reads_remaining {
+            0 | 1 => None,
+            n => Some(SlugRecord {
+                reads_remaining: Some(n - 1),
+                ..expected.clone()
+            }),
+        };
+
+        self.swap_slug_if_unchanged(slug, expected, next.as_ref())
+    }
+
+    fn iter_slugs(&self) -> Iter<'_, (String, SlugRecord)> {
+        self.paged(|conn, after: Option<&String>| {
+            conn.prepare_cached(
+                "SELECT * FROM slugs WHERE ?1 IS NULL OR slug > ?1 ORDER BY slug LIMIT ?2",
+            )?
+            .query_map(params![after, PAGE_SIZE], |row| {
+                Ok((row.get("slug")?, slug_record(row)?))
+            })?
+            .collect()
+        })
+    }
+
+    fn insert_paste(
+        &self,
+        slug: &str,
+        record: &SlugRecord,
+        doc: &DocumentRecord,
+        revision: &RevisionRecord,
+    ) -> Result<bool, Error> {
+        let key = normalize_slug(slug);
+        self.transaction(|conn| {
+            if get_slug(conn, &key)?.is_some_and(|existing| !existing.is_expired()) {
+                return Ok(false);
+            }
+            if let Some(target) = get_alias(conn, &key)? {
+                if is_live_alias(conn, &key, &target)? {
+                    return Ok(false);
+                }
+                conn.execute("DELETE FROM aliases WHERE alias = ?1", [&key])?;
+            }
+
+            put_slug(conn, &key, record)?;
+            put_document(conn, &record.document_hash, doc)?;
+            put_revisions(conn, &key, std::slice::from_ref(revision))?;
+            if let Some(at) = record.expires_at {
+                put_expiry(conn, at, &key)?;
+            }
+
+            Ok(true)
+        })
+    }
+
+    fn move_slug(
+        &self,
+        slug: &str,
+        new_slug: &str,
+        expected: &SlugRecord,
+    ) -> Result<MoveOutcome, Error> {
+        let old_key = normalize_slug(slug);
+        let new_key = normalize_slug(new_slug);
+
+        let mut record = SlugRecord {
+            display_slug: new_slug.to_string(),
+            ..expected.clone()
+        };
+        if new_key != old_key {
+            record.aliases.retain(|alias| *alias != new_key);
+            record.aliases.push(old_key.clone());
+        }
+
+        self.transaction(|conn| {
+            if get_slug(conn, &old_key)?.as_ref() != Some(expected) {
+                return Ok(MoveOutcome::Changed);
+            }
+
+            if new_key != old_key {
+                if get_slug(conn, &new_key)?.is_some_and(|existing| !existing.is_expired()) {
+                    return Ok(MoveOutcome::Taken);
+                }
+                if let Some(target) = get_alias(conn, &new_key)? {
+                    if target != old_key && is_live_alias(conn, &new_key, &target)? {
+                        return Ok(MoveOutcome::Taken);
+                    }
+                    conn.execute("DELETE FROM aliases WHERE alias = ?1", [&new_key])?;
+                }
+
+                delete_slug(conn, &old_key)?;
+                // don't inherit the history of an expired paste that held the new slug
+                conn.execute("DELETE FROM revisions WHERE slug = ?1", [&new_key])?;
+                conn.execute(
+                    "UPDATE revisions SET slug = ?2 WHERE slug = ?1",
+                    [&old_key, &new_key],
+                )?;
+                if let Some(at) = expected.expires_at {
+                    delete_expiry(conn, at, &old_key)?;
+                    put_expiry(conn, at, &new_key)?;
+                }
+                for alias in &record.aliases {
+                    conn.execute(
+                        "INSERT OR REPLACE INTO aliases (alias, slug) VALUES (?1, ?2)",
+                        [alias, &new_key],
+                    )?;
+                }
+            }
+
+            put_slug(conn, &new_key, &record)?;
+            Ok(MoveOutcome::Moved(record.clone()))
+        })
+    }
+
+    fn resolve_alias(&self, slug: &str) -> Result<Option<SlugRecord>, Error> {
+        let key = normalize_slug(slug);
+        let conn = self.lock();
+        let Some(target) = get_alias(&conn, &key)? else {
+            return Ok(None);
+        };
+
+        let record = get_slug(&conn, &target)?;
+        Ok(record.filter(|record| record.aliases.contains(&key)))
+    }
+
+    fn insert_revisions(
+        &self,
+        slug: &str,
+        revisions: &[RevisionRecord],
+    ) -> Result<Option<Vec<RevisionRecord>>, Error> {
+        let key = normalize_slug(slug);
+        self.transaction(|conn| {
+            let previous = get_revisions(conn, &key)?;
+            put_revisions(conn, &key, revisions)?;
+            Ok((!previous.is_empty()).then_some(previous))
+        })
+    }
+
+    fn commit_edit(
+        &self,
+        slug: &str,
+        expected: &SlugRecord,
+        record: &SlugRecord,
+        revision: &RevisionRecord,
+    ) -> Result<bool, Error> {
+        let key = normalize_slug(slug);
+        self.transaction(|conn| {
+            if get_slug(conn, &key)?.as_ref() != Some(expected) {
+                return Ok(false);
+            }
+
+            let number: usize = conn.query_row(
+                "SELECT COALESCE(MAX(number), 0) + 1 FROM revisions WHERE slug = ?1",
+                [&key],
+                |row| row.get(0),
+            )?;
+            put_slug(conn, &key, record)?;
+            insert_revision(conn, &key, number, revision)?;
+            if let Some(at) = record.expires_at {
+                put_expiry(conn, at, &key)?;
+            }
+
+            Ok(true)
+        })
+    }
+
+    fn get_revisions(&self, slug: &str) -> Result<Vec<RevisionRecord>, Error> {
+        get_revisions(&self.lock(), &normalize_slug(slug))
+    }
+
+    fn remove_revisions(&self, slug: &str) -> Result<Option<Vec<RevisionRecord>>, Error> {
+        let key = normalize_slug(slug);
+        self.transaction(|conn| {
+            let previous = get_revisions(conn, &key)?;
+            conn.execute("DELETE FROM revisions WHERE slug = ?1", [&key])?;
+            Ok((!previous.is_empty()).then_some(previous))
+        })
+    }
+
+    fn iter_revisions(&self) -> Iter<'_, (String, Vec<RevisionRecord>)> {
+        let slugs = self.paged(|conn, after: Option<&String>| {
+            conn.prepare_cached(
+                "SELECT DISTINCT slug FROM revisions
+                 WHERE ?1 IS NULL OR slug > ?1 ORDER BY slug LIMIT ?2",
+            )?
+            .query_map(params![after, PAGE_SIZE], |row| Ok((row.get(0)?, ())))?
+            .collect()
+        });
+
+        Box::new(slugs.map(|entry| {
+            let (slug, ()) = entry?;
+            let revisions = self.get_revisions(&slug)?;
+            Ok((slug, revisions))
+        }))
+    }
+
+    fn remove_expiry(&self, at: DateTime<Utc>, slug: &str) -> Result<(), Error> {
+        delete_expiry(&self.lock(), at, &normalize_slug(slug))
+    }
+
+    fn iter_expired(&self, now: DateTime<Utc>) -> Iter<'_, (DateTime<Utc>, String)> {
+        let entries = self.paged(move |conn, after: Option<&(i64, String)>| {
+            let (after_at, after_slug) = after.cloned().unzip();
+            conn.prepare_cached(
+                "SELECT at, slug FROM expiry
+                 WHERE at < ?1 AND (?2 IS NULL OR (at, slug) > (?2, ?3))
+                 ORDER BY at, slug LIMIT ?4",
+            )?
+            .query_map(
+                params![expiry_millis(now), after_at, after_slug, PAGE_SIZE],
+                |row| Ok(((row.get(0)?, row.get(1)?), ())),
+            )?
+            .collect()
+        });
+
+        Box::new(entries.map(|entry| {
+            let ((at, slug), ()) = entry?;
+            Ok((
+                DateTime::from_timestamp_millis(at).unwrap_or_default(),
+                slug,
+            ))
+        }))
+    }
+
+    fn insert_block_rule(
+        &self,
+        pattern: &str,
+        rule: &BlockRule,
+    ) -> Result<Option<BlockRule>, Error> {
+        self.transaction(|conn| {
+            let previous = get_block_rule(conn, pattern)?;
+            conn.execute(
+                "INSERT OR REPLACE INTO blocklist (pattern, kind, created) VALUES (?1, ?2, ?3)",
+                params![pattern, rule.kind, rule.created],
+            )?;
+            Ok(previous)
+        })
+    }
+
+    fn remove_block_rule(&self, pattern: &str) -> Result<Option<BlockRule>, Error> {
+        self.transaction(|conn| {
+            let previous = get_block_rule(conn, pattern)?;
+            conn.execute("DELETE FROM blocklist WHERE pattern = ?1", [pattern])?;
+            Ok(previous)
+        })
+    }
+
+    fn iter_block_rules(&self) -> Iter<'_, (String, BlockRule)> {
+        self.paged(|conn, after: Option<&String>| {
+            conn.prepare_cached(
+                "SELECT pattern, kind, created FROM blocklist
+                 WHERE ?1 IS NULL OR pattern > ?1 ORDER BY pattern LIMIT ?2",
+            )?
+            .query_map(params![after, PAGE_SIZE], |row| {
+                Ok((row.get("pattern")?, block_rule(row)?))
+            })?
+            .collect()
+        })
+    }
+
+    fn iter_slug_collisions(&self) -> Iter<'_, (String, SlugCollision)> {
+        // collisions only come out of migrating sled databases from before
+        // slugs were case-insensitive
+        Box::new(std::iter::empty())
+    }
+}
+
+fn get_slug(conn: &Connection, key: &str) -> Result<Option<SlugRecord>, Error> {
+    let record = conn
+        .prepare_cached("SELECT * FROM slugs WHERE slug = ?1")?
+        .query_row([key], slug_record)
+        .optional()?;
+    Ok(record)
+}
+
+fn put_slug(conn: &Connection, key: &str, record: &SlugRecord) -> Result<(), Error> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO slugs (
+            slug, display_slug, document_hash, edit_code, expires_at,
+            reads_remaining, aliases, view_password, encrypted
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )?
+    .execute(params![
+        key,
+        record.display_slug,
+        record.document_hash,
+        record.edit_code,
+        record.expires_at,
+        record.reads_remaining,
+        record.aliases.join(","),
+        record.view_password,
+        record.encrypted,
+    ])?;
+    Ok(())
+}
+
+fn delete_slug(conn: &Connection, key: &str) -> Result<(), Error> {
+    conn.execute("DELETE FROM slugs WHERE slug = ?1", [key])?;
+    Ok(())
+}
+
+fn slug_record(row: &Row) -> rusqlite::Result<SlugRecord> {
+    let aliases: String = row.get("aliases")?;
+    Ok(SlugRecord {
+        document_hash: row.get("document_hash")?,
+        edit_code: row.get("edit_code")?,
+        expires_at: row.get("expires_at")?,
+        reads_remaining: row.get("reads_remaining")?,
+        display_slug: row.get("display_slug")?,
+        aliases: aliases
+            .split(',')
+            .filter(|alias| !alias.is_empty())
+            .map(String::from)
+            .collect(),
+        view_password: row.get("view_password")?,
+        encrypted: row.get("encrypted")?,
+    })
+}
+
+fn get_document(conn: &Connection, hash: &DocumentHash) -> Result<Option<DocumentRecord>, Error> {
+    let doc = conn
+        .prepare_cached("SELECT content, created FROM documents WHERE hash = ?1")?
+        .query_row([hash], document_record)
+        .optional()?;
+    Ok(doc)
+}
+
+fn put_document(conn: &Connection, hash: &DocumentHash, doc: &DocumentRecord) -> Result<(), Error> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO documents (hash, content, created) VALUES (?1, ?2, ?3)",
+    )?
+    .execute(params![hash, doc.content, doc.created])?;
+    Ok(())
+}
+
+fn document_record(row: &Row) -> rusqlite::Result<DocumentRecord> {
+    Ok(DocumentRecord {
+        content: row.get("content")?,
+        created: row.get("created")?,
+    })
+}
+
+fn get_revisions(conn: &Connection, key: &str) -> Result<Vec<RevisionRecord>, Error> {
+    let revisions = conn
+        .prepare_cached(
+            "SELECT document_hash, created, message FROM revisions
+             WHERE slug = ?1 ORDER BY number",
+        )?
+        .query_map([key], |row| {
+            Ok(RevisionRecord {
+                document_hash: row.get("document_hash")?,
+                created: row.get("created")?,
+                message: row.get("message")?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(revisions)
+}
+
+fn put_revisions(conn: &Connection, key: &str, revisions: &[RevisionRecord]) -> Result<(), Error> {
+    conn.execute("DELETE FROM revisions WHERE slug = ?1", [key])?;
+    for (i, revision) in revisions.iter().enumerate() {
+        insert_revision(conn, key, i + 1, revision)?;
+    }
+    Ok(())
+}
+
+fn insert_revision(
+    conn: &Connection,
+    key: &str,
+    number: usize,
+    revision: &RevisionRecord,
+) -> Result<(), Error> {
+    conn.prepare_cached(
+        "INSERT INTO revisions (slug, number, document_hash, created, message)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?
+    .execute(params![
+        key,
+        number,
+        revision.document_hash,
+        revision.created,
+        revision.message,
+    ])?;
+    Ok(())
+}
+
+/// Expiry entries are kept to the millisecond, like the keys of sled's `expiry` tree.
+fn expiry_millis(at: DateTime<Utc>) -> i64 {
+    at.timestamp_millis().max(0)
+}
+
+fn put_expiry(conn: &Connection, at: DateTime<Utc>, key: &str) -> Result<(), Error> {
+    conn.execute(
+        "INSERT OR IGNORE INTO expiry (at, slug) VALUES (?1, ?2)",
+        params![expiry_millis(at), key],
+    )?;
+    Ok(())
+}
+
+fn delete_expiry(conn: &Connection, at: DateTime<Utc>, key: &str) -> Result<(), Error> {
+    conn.execute(
+        "DELETE FROM expiry WHERE at = ?1 AND slug = ?2",
+        params![expiry_millis(at), key],
+    )?;
+    Ok(())
+}
+
+fn get_alias(conn: &Connection, key: &str) -> Result<Option<String>, Error> {
+    let target = conn
+        .prepare_cached("SELECT slug FROM aliases WHERE alias = ?1")?
+        .query_row([key], |row| row.get(0))
+        .optional()?;
+    Ok(target)
+}
+
+/// Whether the alias `key` still leads to `target`. Aliases are not removed
+/// along with their paste, so they only count while the paste they point to
+/// is alive and still lists them.
+fn is_live_alias(conn: &Connection, key: &str, target: &str) -> Result<bool, Error> {
+    let Some(record) = get_slug(conn, target)? else {
+        return Ok(false);
+    };
+    Ok(!record.is_expired() && record.aliases.iter().any(|alias| alias == key))
+}
+
+fn get_block_rule(conn: &Connection, pattern: &str) -> Result<Option<BlockRule>, Error> {
+    let rule = conn
+        .prepare_cached("SELECT kind, created FROM blocklist WHERE pattern = ?1")?
+        .query_row([pattern], block_rule)
+        .optional()?;
+    Ok(rule)
+}
+
+fn block_rule(row: &Row) -> rusqlite::Result<BlockRule> {
+    Ok(BlockRule {
+        kind: row.get("kind")?,
+        created: row.get("created")?,
+    })
+}
+
+impl ToSql for DocumentHash {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.to_string().into())
+    }
+}
+
+impl FromSql for DocumentHash {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e| FromSqlError::Other(Box::new(e)))
+    }
+}
+
+impl ToSql for PatternKind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let kind = match self {
+            PatternKind::Exact => "exact",
+            PatternKind::Glob => "glob",
+            PatternKind::Regex => "regex",
+        };
+        Ok(kind.into())
+    }
+}
+
+impl FromSql for PatternKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "exact" => Ok(PatternKind::Exact),
+            "glob" => Ok(PatternKind::Glob),
+            "regex" => Ok(PatternKind::Regex),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
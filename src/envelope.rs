@@ -0,0 +1,212 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// The only envelope layout understood so far.
+pub const ENVELOPE_VERSION: u32 = 1;
+
+/// Length of a ChaCha20-Poly1305 nonce in bytes.
+pub const NONCE_LEN: usize = 12;
+
+/// Length of the Poly1305 tag at the end of the ciphertext in bytes.
+pub const TAG_LEN: usize = 16;
+
+/// The content of an encrypted paste, as produced by the client.
+///
+/// The server never sees the key, which stays in the fragment of the paste
+/// URL, so it can only check that the envelope is well formed. Binary fields
+/// are base64url encoded without padding.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Envelope {
+    pub version: u32,
+    pub kdf: Kdf,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+/// How the client turns the secret in the URL fragment into a key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Kdf {
+    /// The fragment holds the 32 byte key itself.
+    None,
+    /// The fragment holds a passphrase, stretched with Argon2id.
+    Argon2id {
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+        salt: String,
+    },
+}
+
+/// Why some content is not a valid [`Envelope`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum EnvelopeError {
+    #[error("malformed envelope: {0}")]
+    Malformed(String),
+
+    #[error("unsupported envelope version {0}")]
+    UnsupportedVersion(u32),
+
+    #[error("{0} is not valid base64url")]
+    Encoding(&'static str),
+
+    #[error("nonce must be {NONCE_LEN} bytes")]
+    Nonce,
+
+    #[error("ciphertext must hold at least the {TAG_LEN} byte tag")]
+    Ciphertext,
+
+    #[error("argon2id parameters are out of range")]
+    KdfParams,
+
+    #[error("salt must be between 16 and 64 bytes")]
+    Salt,
+}
+
+impl Envelope {
+    /// Parses and validates the content of an encrypted paste.
+    pub fn parse(content: &str) -> Result<Self, EnvelopeError> {
+        let envelope: Envelope =
+            serde_json::from_str(content).map_err(|e| EnvelopeError::Malformed(e.to_string()))?;
+        envelope.validate()?;
+        Ok(envelope)
+    }
+
+    fn validate(&self) -> Result<(), EnvelopeError> {
+        if self.version != ENVELOPE_VERSION {
+            return Err(EnvelopeError::UnsupportedVersion(self.version));
+        }
+        if decode(&self.nonce, "nonce")?.len() != NONCE_LEN {
+            return Err(EnvelopeError::Nonce);
+        }
+        if decode(&self.ciphertext, "ciphertext")?.len() < TAG_LEN {
+            return Err(EnvelopeError::Ciphertext);
+        }
+
+        if let Kdf::Argon2id {
+            memory_kib,
+            iterations,
+            parallelism,
+            ref salt,
+        } = self.kdf
+        {
+            // generous enough for any sane client, small enough not to hang a browser
+            if !(1..=16).contains(&parallelism)
+                || !(8 * parallelism..=1 << 20).contains(&memory_kib)
+                || !(1..=16).contains(&iterations)
+            {
+                return Err(EnvelopeError::KdfParams);
+            }
+            if !(16..=64).contains(&decode(salt, "salt")?.len()) {
+                return Err(EnvelopeError::Salt);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn decode(field: &str, name: &'static str) -> Result<Vec<u8>, EnvelopeError> {
+    URL_SAFE_NO_PAD
+        .decode(field)
+        .map_err(|_| EnvelopeError::Encoding(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn envelope(kdf: &str, nonce_len: usize, ciphertext_len: usize) -> String {
+        format!(
+            r#"{{"version":1,"kdf":{kdf},"nonce":"{}","ciphertext":"{}"}}"#,
+            URL_SAFE_NO_PAD.encode(vec![7; nonce_len]),
+            URL_SAFE_NO_PAD.encode(vec![9; ciphertext_len]),
+        )
+    }
+
+    fn argon2id(memory_kib: u32, salt_len: usize) -> String {
+        format!(
+            r#"{{"name":"argon2id","memory_kib":{memory_kib},"iterations":3,"parallelism":1,"salt":"{}"}}"#,
+            URL_SAFE_NO_PAD.encode(vec![1; salt_len]),
+        )
+    }
+
+    #[test]
+    fn accepts_well_formed_envelopes() {
+        let parsed = Envelope::parse(&envelope(r#"{"name":"none"}"#, NONCE_LEN, 40)).unwrap();
+        assert_eq!(parsed.kdf, Kdf::None);
+
+        let parsed = Envelope::parse(&envelope(&argon2id(19456, 16), NONCE_LEN, TAG_LEN)).unwrap();
+        assert!(matches!(
+            parsed.kdf,
+            Kdf::Argon2id {
+                memory_kib: 19456,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn rejects_content_that_is_not_an_envelope() {
+        for content in ["# hello", "{}", r#"{"version":1}"#, ""] {
+            assert!(matches!(
+                Envelope::parse(content),
+                Err(EnvelopeError::Malformed(_))
+            ));
+        }
+
+        let extra = envelope(r#"{"name":"none"}"#, NONCE_LEN, 40).replace('}', r#","key":"x"}"#);
+        assert!(matches!(
+            Envelope::parse(&extra),
+            Err(EnvelopeError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        let content = envelope(r#"{"name":"none"}"#, NONCE_LEN, 40)
+            .replace(r#""version":1"#, r#""version":2"#);
+        assert_eq!(
+            Envelope::parse(&content),
+            Err(EnvelopeError::UnsupportedVersion(2))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_nonces_and_ciphertexts() {
+        let none = r#"{"name":"none"}"#;
+        assert_eq!(
+            Envelope::parse(&envelope(none, 24, 40)),
+            Err(EnvelopeError::Nonce)
+        );
+        assert_eq!(
+            Envelope::parse(&envelope(none, NONCE_LEN, TAG_LEN - 1)),
+            Err(EnvelopeError::Ciphertext)
+        );
+
+        let padded =
+            envelope(none, NONCE_LEN, 40).replace(r#"","ciphertext""#, r#"==","ciphertext""#);
+        assert_eq!(
+            Envelope::parse(&padded),
+            Err(EnvelopeError::Encoding("nonce"))
+        );
+    }
+
+    #[test]
+    fn rejects_unreasonable_kdf_parameters() {
+        assert_eq!(
+            Envelope::parse(&envelope(&argon2id(4, 16), NONCE_LEN, 40)),
+            Err(EnvelopeError::KdfParams)
+        );
+        assert_eq!(
+            Envelope::parse(&envelope(&argon2id(1 << 21, 16), NONCE_LEN, 40)),
+            Err(EnvelopeError::KdfParams)
+        );
+        assert_eq!(
+            Envelope::parse(&envelope(&argon2id(19456, 8), NONCE_LEN, 40)),
+            Err(EnvelopeError::Salt)
+        );
+    }
+}
@@ -15,12 +15,55 @@ remove auto conversion from error to api error.
 /// # Variants:
 ///
 /// - `Sled`: Wraps errors originating from the `sled` database interactions.
+/// - `Sqlite`: Wraps errors from the SQLite database.
 /// - `Bincode`: Encapsulates serialization and deserialization errors from the `bincode` crate.
+/// - `PasswordHash`: Wraps errors from hashing edit codes with `argon2`.
+/// - `Cipher`: Records that could not be encrypted or decrypted at rest.
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("Sled Error: {0}")]
     Sled(#[from] sled::Error),
 
+    #[error("SQLite Error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
     #[error("Bincode Error: {0}")]
     Bincode(#[from] bincode::Error),
+
+    #[error("Password Hash Error: {0}")]
+    PasswordHash(#[from] argon2::password_hash::Error),
+
+    #[error("Cipher Error: {0}")]
+    Cipher(#[from] crate::cipher::CipherError),
+}
+
+impl Error {
+    /// Names the kind of failure for the logs, telling apart a corrupt
+    /// database from IO trouble and from records that no longer decode.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Sled(sled::Error::Corruption { .. }) => "database corruption",
+            Error::Sled(sled::Error::Io(_)) => "database io",
+            Error::Sled(sled::Error::ReportableBug(_)) => "sled bug",
+            Error::Sled(_) => "database",
+            Error::Sqlite(rusqlite::Error::SqliteFailure(e, _))
+                if e.code == rusqlite::ErrorCode::DatabaseCorrupt =>
+            {
+                "database corruption"
+            }
+            Error::Sqlite(_) => "database",
+            Error::Bincode(_) => "serialization",
+            Error::PasswordHash(_) => "password hashing",
+            Error::Cipher(_) => "encryption",
+        }
+    }
+}
+
+impl From<sled::transaction::TransactionError<Error>> for Error {
+    fn from(e: sled::transaction::TransactionError<Error>) -> Self {
+        match e {
+            sled::transaction::TransactionError::Abort(e) => e,
+            sled::transaction::TransactionError::Storage(e) => Error::Sled(e),
+        }
+    }
 }
@@ -1,15 +1,26 @@
 // USE "JetBrains Mono"
 
 use axum::Extension;
+use clap::Parser;
+use config::{Cli, Command, Config};
 use routes::configure_routes;
+use services::{collect_garbage, hash_legacy_edit_codes, GC_GRACE_PERIOD};
 use state::AppState;
+use tasks::{spawn_expiry_reaper, spawn_garbage_collector, spawn_reencryption};
 
+mod auth;
+mod blocklist;
+mod cipher;
+mod config;
 mod db;
+mod envelope;
 mod errors;
+mod merge;
 mod routes;
 mod state;
 mod validators;
 mod services;
+mod tasks;
 
 // #[cfg(not(target_env = "msvc"))]
 // use jemallocator::Jemalloc;
@@ -23,10 +34,56 @@ async fn main() {
 
     env_logger::init();
 
-    let app_state = AppState::new("./database");
+    let cli = Cli::parse();
+    let config = match Config::load(&cli) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("invalid configuration: {e}");
+            std::process::exit(2);
+        }
+    };
+
+    let app_state = AppState::new(config);
+
+    match cli.command {
+        Some(Command::Gc) => {
+            let removed = collect_garbage(&app_state.db, GC_GRACE_PERIOD).expect("failed to collect garbage");
+            println!("removed {removed} orphaned documents");
+            return;
+        }
+        Some(Command::Reencrypt) => {
+            let rewritten = app_state.db.reencrypt().expect("failed to re-encrypt records");
+            println!("re-encrypted {rewritten} records");
+            return;
+        }
+        Some(Command::HashEditCodes) => {
+            let updated =
+                hash_legacy_edit_codes(&app_state.db).expect("failed to hash edit codes");
+            println!("hashed {updated} plaintext edit codes");
+            return;
+        }
+        Some(Command::SlugCollisions) => {
+            for entry in app_state.db.iter_slug_collisions() {
+                let (slug, collision) = entry.expect("failed to read slug collisions");
+                println!(
+                    "{slug}\t{}\t{} revisions",
+                    collision.record.document_hash,
+                    collision.revisions.len()
+                );
+            }
+            return;
+        }
+        None => {}
+    }
+
+    spawn_garbage_collector(app_state.db.clone(), app_state.config.gc_interval());
+    spawn_expiry_reaper(app_state.db.clone(), app_state.config.reap_interval());
+    spawn_reencryption(app_state.db.clone());
+
+    let bind = app_state.config.bind;
     let app_routes = configure_routes()
         .layer(Extension(app_state));
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+    let listener = tokio::net::TcpListener::bind(bind).await.unwrap();
     axum::serve(listener, app_routes).await.unwrap();
 }
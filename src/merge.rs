@@ -0,0 +1,166 @@
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+/// The result of merging two edits of the same document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Merge {
+    /// Both edits applied without touching the same lines.
+    Clean(String),
+    /// Some lines were changed by both edits; `content` holds both versions
+    /// of those lines between conflict markers.
+    Conflicted { content: String, conflicts: usize },
+}
+
+/// Merges `ours` and `theirs`, two edits of `base`, line by line.
+///
+/// Hunks changed on only one side are taken from that side, and hunks changed
+/// the same way on both sides are taken once. Anything else is a conflict.
+pub fn merge_lines(base: &str, ours: &str, theirs: &str) -> Merge {
+    let base: Vec<&str> = base.split_inclusive('\n').collect();
+    let ours: Vec<&str> = ours.split_inclusive('\n').collect();
+    let theirs: Vec<&str> = theirs.split_inclusive('\n').collect();
+
+    let in_ours = matching_lines(&base, &ours);
+    let in_theirs = matching_lines(&base, &theirs);
+
+    let mut content = String::new();
+    let mut conflicts = 0;
+    let (mut i, mut j, mut k) = (0, 0, 0);
+
+    loop {
+        // the next base line kept by both sides, where the three versions line up again
+        let sync = (i..base.len()).find_map(|n| Some((n, in_ours[n]?, in_theirs[n]?)));
+        let (i_end, j_end, k_end) = sync.unwrap_or((base.len(), ours.len(), theirs.len()));
+
+        let (b, o, t) = (&base[i..i_end], &ours[j..j_end], &theirs[k..k_end]);
+        if o == b || o == t {
+            content.extend(t.iter().copied());
+        } else if t == b {
+            content.extend(o.iter().copied());
+        } else {
+            conflicts += 1;
+            push_conflict(&mut content, b, o, t);
+        }
+
+        let Some((i_sync, j_sync, k_sync)) = sync else {
+            break;
+        };
+        content.push_str(base[i_sync]);
+        (i, j, k) = (i_sync + 1, j_sync + 1, k_sync + 1);
+    }
+
+    if conflicts == 0 {
+        Merge::Clean(content)
+    } else {
+        Merge::Conflicted { content, conflicts }
+    }
+}
+
+/// For every line of `base`, the index of the same line in `other` if it was kept.
+fn matching_lines(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; base.len()];
+
+    for op in capture_diff_slices(Algorithm::Myers, base, other) {
+        if let DiffOp::Equal {
+            old_index,
+            new_index,
+            len,
+        } = op
+        {
+            for n in 0..len {
+                matches[old_index + n] = Some(new_index + n);
+            }
+        }
+    }
+
+    matches
+}
+
+/// Writes a hunk changed on both sides in the diff3 conflict marker style.
+fn push_conflict(content: &mut String, base: &[&str], ours: &[&str], theirs: &[&str]) {
+    let mut push_section = |marker: &str, lines: &[&str]| {
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(marker);
+        content.push('\n');
+        content.extend(lines.iter().copied());
+    };
+
+    push_section("<<<<<<< yours", ours);
+    push_section("||||||| base", base);
+    push_section("=======", theirs);
+    push_section(">>>>>>> current", &[]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_to_different_lines_merge_cleanly() {
+        assert_eq!(
+            merge_lines("a\nb\nc\n", "A\nb\nc\n", "a\nb\nC\n"),
+            Merge::Clean("A\nb\nC\n".into())
+        );
+        assert_eq!(
+            merge_lines("a\nb\n", "a\nb\nours\n", "theirs\na\nb\n"),
+            Merge::Clean("theirs\na\nb\nours\n".into())
+        );
+    }
+
+    #[test]
+    fn the_same_change_on_both_sides_is_taken_once() {
+        assert_eq!(
+            merge_lines("a\nb\nc\n", "a\nB\nc\n", "a\nB\nc\n"),
+            Merge::Clean("a\nB\nc\n".into())
+        );
+    }
+
+    #[test]
+    fn lines_changed_on_both_sides_conflict() {
+        assert_eq!(
+            merge_lines("a\nb\nc\n", "a\nX\nc\n", "a\nY\nc\n"),
+            Merge::Conflicted {
+                content: "a\n<<<<<<< yours\nX\n||||||| base\nb\n=======\nY\n>>>>>>> current\nc\n"
+                    .into(),
+                conflicts: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn deletions_are_merged_like_other_changes() {
+        assert_eq!(
+            merge_lines("a\nb\nc\n", "a\nc\n", "a\nb\nc\nd\n"),
+            Merge::Clean("a\nc\nd\n".into())
+        );
+
+        // deleting a line the other side changed loses neither silently
+        assert_eq!(
+            merge_lines("a\nb\nc\n", "a\nc\n", "a\nB\nc\n"),
+            Merge::Conflicted {
+                content: "a\n<<<<<<< yours\n||||||| base\nb\n=======\nB\n>>>>>>> current\nc\n"
+                    .into(),
+                conflicts: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn content_without_a_trailing_newline_is_kept_as_is() {
+        assert_eq!(
+            merge_lines("a\nb\nc", "A\nb\nc", "a\nb\nC"),
+            Merge::Clean("A\nb\nC".into())
+        );
+
+        // markers always start on their own line
+        assert_eq!(
+            merge_lines("a\nb", "a\nX", "a\nY"),
+            Merge::Conflicted {
+                content: "a\n<<<<<<< yours\nX\n||||||| base\nb\n=======\nY\n>>>>>>> current\n"
+                    .into(),
+                conflicts: 1,
+            }
+        );
+    }
+}
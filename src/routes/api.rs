@@ -1,53 +1,256 @@
-use askama_axum::IntoResponse;
+use std::ops::Deref;
+
+use askama_axum::{IntoResponse, Response};
 use axum::{
-    extract,
-    http::StatusCode,
+    async_trait,
+    extract::{self, rejection::JsonRejection, FromRequest, Request},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::{self, Next},
     routing::{delete, get, post, put},
     Extension, Json, Router,
 };
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use log::error;
 use nanoid::nanoid;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 use crate::{
-    db::{Database, DocumentHash, DocumentRecord, SlugRecord},
+    auth::{
+        hash_edit_code, hash_view_password, spawn_argon2, verify_edit_code, verify_view_cookie,
+        view_cookie_name, EditCodeMatch, ViewPasswordCheck,
+    },
+    blocklist::{compile as compile_pattern, is_reserved_slug},
+    db::{Database, DocumentHash, DocumentRecord, PatternKind, RevisionRecord, SlugRecord},
     errors::Error,
-    services::{create_paste, markdown_to_html, markdown_to_html_pretty},
+    merge::Merge,
+    routes::cache::{http_date, CachePolicy, Validator},
+    services::{
+        change_edit_code, collect_garbage, consume_read, create_paste, diff_versions, edit_paste,
+        merge_stale_edit, paste_revisions, rename_paste, unified_diff, upgrade_edit_code,
+        EditOutcome, PasteSettings, RenameOutcome, RenderOptions, Version, DEFAULT_THEME,
+        GC_GRACE_PERIOD,
+    },
     state::AppState,
-    validators::{is_invalid_document, is_invalid_edit_code, is_invalid_slug},
+    validators::{validate_document, validate_envelope, Validate, ValidationError},
 };
 
-use super::frontend::MarkdownPreview;
+/// A stable, machine-readable reason for a failed request, so clients do not
+/// have to match on `message`, which is meant for humans and may change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidSlug,
+    SlugReserved,
+    InvalidEditCode,
+    DocumentTooLarge,
+    EditMessageTooLong,
+    InvalidExpiry,
+    InvalidBurnAfterReads,
+    InvalidHash,
+    UnknownTheme,
+    InvalidPattern,
+    InvalidViewPassword,
+    InvalidEnvelope,
+    InvalidBody,
+    SlugTaken,
+    Unauthorized,
+    ViewPasswordRequired,
+    Forbidden,
+    NotFound,
+    Gone,
+    StaleBase,
+    MergeConflict,
+    EncryptedPaste,
+    TooManyAttempts,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidSlug
+            | ErrorCode::SlugReserved
+            | ErrorCode::InvalidEditCode
+            | ErrorCode::DocumentTooLarge
+            | ErrorCode::EditMessageTooLong
+            | ErrorCode::InvalidExpiry
+            | ErrorCode::InvalidBurnAfterReads
+            | ErrorCode::InvalidHash
+            | ErrorCode::UnknownTheme
+            | ErrorCode::InvalidPattern
+            | ErrorCode::InvalidViewPassword
+            | ErrorCode::InvalidEnvelope
+            | ErrorCode::InvalidBody => StatusCode::BAD_REQUEST,
+            ErrorCode::SlugTaken | ErrorCode::MergeConflict => StatusCode::CONFLICT,
+            ErrorCode::Unauthorized | ErrorCode::ViewPasswordRequired => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::Gone => StatusCode::GONE,
+            ErrorCode::StaleBase => StatusCode::PRECONDITION_FAILED,
+            ErrorCode::EncryptedPaste => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::TooManyAttempts => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsonError {
+    pub code: ErrorCode,
     pub message: String,
+    pub field: Option<String>, // the request field or header that was rejected
+    #[serde(flatten)]
+    pub details: Map<String, Value>, // further members particular to the code
 }
 
-pub struct JsonErrorResponse(StatusCode, String);
+#[derive(Debug)]
+pub struct JsonErrorResponse(JsonError);
+
+impl JsonErrorResponse {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        JsonErrorResponse(JsonError {
+            code,
+            message: message.into(),
+            field: None,
+            details: Map::new(),
+        })
+    }
+
+    pub fn with_field(mut self, field: impl Into<String>) -> Self {
+        self.0.field = Some(field.into());
+        self
+    }
+
+    pub fn with_detail(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.0.details.insert(name.to_string(), value.into());
+        self
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.0.code.status()
+    }
+}
 
 impl IntoResponse for JsonErrorResponse {
     fn into_response(self) -> askama_axum::Response {
-        (
-            self.0,
-            Json(JsonError {
-                message: self.1.into(),
-            }),
-        )
-            .into_response()
+        let mut response = (self.status(), Json(self.0.clone())).into_response();
+        // kept so `problem_json` can rewrite the body for clients that ask for it
+        response.extensions_mut().insert(self.0);
+        response
+    }
+}
+
+impl From<ValidationError> for JsonErrorResponse {
+    fn from(e: ValidationError) -> Self {
+        let code = match e {
+            ValidationError::Slug { .. } => ErrorCode::InvalidSlug,
+            ValidationError::ReservedSlug => ErrorCode::SlugReserved,
+            ValidationError::EditCode { .. } => ErrorCode::InvalidEditCode,
+            ValidationError::DocumentTooLarge { .. } => ErrorCode::DocumentTooLarge,
+            ValidationError::EditMessageTooLong { .. } => ErrorCode::EditMessageTooLong,
+            ValidationError::BurnAfterReads => ErrorCode::InvalidBurnAfterReads,
+            ValidationError::ViewPassword { .. } => ErrorCode::InvalidViewPassword,
+            ValidationError::Envelope(_) => ErrorCode::InvalidEnvelope,
+        };
+
+        JsonErrorResponse::new(code, e.to_string()).with_field(e.field())
     }
 }
 
 impl From<Error> for JsonErrorResponse {
     fn from(e: Error) -> Self {
-        error!("Internal server error: {e}");
+        error!("Internal server error ({}): {e}", e.kind());
 
-        JsonErrorResponse(
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "internal server error".into(),
-        )
+        JsonErrorResponse::new(ErrorCode::Internal, "internal server error")
+    }
+}
+
+/// A JSON request body, like [`Json`], whose rejections are reported as a
+/// [`JsonError`] with the `invalid_body` code rather than as plain text.
+#[derive(Debug)]
+pub struct JsonBody<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for JsonBody<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = JsonErrorResponse;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(request, state).await {
+            Ok(Json(body)) => Ok(JsonBody(body)),
+            Err(rejection) => Err(invalid_body(rejection)),
+        }
+    }
+}
+
+impl<T> Deref for JsonBody<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+fn invalid_body(rejection: JsonRejection) -> JsonErrorResponse {
+    JsonErrorResponse::new(ErrorCode::InvalidBody, rejection.body_text())
+}
+
+/// An RFC 9457 problem details document carrying a [`JsonError`].
+#[derive(Debug, Serialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    code: ErrorCode,
+    field: Option<String>,
+    #[serde(flatten)]
+    details: Map<String, Value>,
+}
+
+/// Sends errors as `application/problem+json` to clients that list it in `Accept`.
+/// Everyone else keeps getting the plain [`JsonError`] body.
+pub async fn problem_json(request: Request, next: Next) -> Response {
+    let wants_problem = request
+        .headers()
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.contains("application/problem+json"));
+
+    let response = next.run(request).await;
+    if !wants_problem {
+        return response;
     }
+    let Some(error) = response.extensions().get::<JsonError>().cloned() else {
+        return response;
+    };
+
+    let status = error.code.status();
+    let problem = ProblemDetails {
+        kind: "about:blank",
+        title: status.canonical_reason().unwrap_or_default(),
+        status: status.as_u16(),
+        detail: error.message,
+        code: error.code,
+        field: error.field,
+        details: error.details,
+    };
+
+    // keep headers such as the `ETag` of a stale edit
+    let (mut parts, _) = response.into_parts();
+    let body = Json(problem).into_response().into_body();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/problem+json"),
+    );
+    Response::from_parts(parts, body)
 }
 
 /// Constructs and returns a router with all API endpoints for managing pastes.
@@ -64,6 +267,8 @@ pub fn api_routes() -> Router {
     Router::new()
         .merge(paste_routes())
         .route("/markdown/render", post(render_markdown_handler))
+        .merge(admin_routes())
+        .layer(middleware::from_fn(problem_json))
 }
 
 /// Constructs a router dedicated to paste management operations.
@@ -83,60 +288,213 @@ pub fn paste_routes() -> Router {
         .route("/pastes/:id", delete(delete_paste_handler)) // delete an existing paste
         .route("/pastes/:id", get(get_paste_handler)) // get a specific paste
         .route("/pastes/:id/html", get(get_paste_html_handler)) // get the html for the specific paste
+        .route("/pastes/:id/raw", get(get_paste_raw_handler)) // get the plain text of the specific paste
+        .route("/pastes/:id/revisions", get(get_revisions_handler)) // list the revisions of a paste
+        .route("/pastes/:id/revisions/:n", get(get_revision_handler)) // get a specific revision of a paste
+        .route("/pastes/:id/diff", get(get_paste_diff_handler)) // diff two revisions of a paste
+        .route("/pastes/:id/rename", post(rename_paste_handler)) // move a paste to a new slug
+        .route("/pastes/:id/edit-code", post(change_edit_code_handler)) // replace the edit code of a paste
 }
 
-pub fn check_slug_format(slug: &str) -> Result<(), JsonErrorResponse> {
-    if is_invalid_slug(slug) {
-        return Err(JsonErrorResponse(
-            StatusCode::BAD_REQUEST,
-            "slug must be between 4 and 16 characters and ascii alphanumeric".into(),
+/// Constructs a router for administering the instance, guarded by the
+/// configured admin token.
+pub fn admin_routes() -> Router {
+    Router::new()
+        .route("/admin/blocklist", get(get_blocklist_handler)) // list blocked slug patterns
+        .route("/admin/blocklist", post(block_slugs_handler)) // block a slug pattern
+        .route("/admin/blocklist", delete(unblock_slugs_handler)) // unblock a slug pattern
+        .route("/admin/gc", post(collect_garbage_handler)) // run a garbage collection pass now
+}
+
+/// Checks the `Authorization: Bearer` token against the configured admin token.
+pub fn check_admin(state: &AppState, headers: &HeaderMap) -> Result<(), JsonErrorResponse> {
+    let Some(ref admin_token) = state.config.admin_token else {
+        return Err(JsonErrorResponse::new(
+            ErrorCode::Forbidden,
+            "the admin API is disabled",
+        ));
+    };
+
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+
+    // comparing hashes keeps the comparison constant time
+    if blake3::hash(token.as_bytes()) != blake3::hash(admin_token.as_bytes()) {
+        return Err(JsonErrorResponse::new(
+            ErrorCode::Unauthorized,
+            "a valid admin token is required",
         ));
     }
     Ok(())
 }
 
-pub fn check_edit_code_format(edit_code: &str) -> Result<(), JsonErrorResponse> {
-    if is_invalid_edit_code(edit_code) {
-        return Err(JsonErrorResponse(
-            StatusCode::BAD_REQUEST,
-            "edit code must be between 4 and 32 characters and valid ascii".into(),
-        ));
+/// Rejects slugs matching a pattern on the admin blocklist.
+pub fn check_slug_not_blocked(state: &AppState, slug: &str) -> Result<(), JsonErrorResponse> {
+    if state.blocklist.is_blocked(slug) {
+        return Err(
+            JsonErrorResponse::new(ErrorCode::SlugReserved, "slug is not available")
+                .with_field("custom_slug"),
+        );
     }
     Ok(())
 }
 
-pub fn check_document(contents: &str) -> Result<(), JsonErrorResponse> {
-    if is_invalid_document(contents) {
-        return Err(JsonErrorResponse(
-            StatusCode::BAD_REQUEST,
-            "document must be 200,000 or less bytes".into(),
-        ));
+/// Resolves the requested expiry of a paste, given either as a point in time
+/// or as a number of seconds from now, into a point in time.
+pub fn check_expiry(
+    expires_at: Option<DateTime<Utc>>,
+    expires_in: Option<u64>,
+) -> Result<Option<DateTime<Utc>>, JsonErrorResponse> {
+    let expires_at = match (expires_at, expires_in) {
+        (None, None) => return Ok(None),
+        (Some(at), None) => Some(at),
+        (None, Some(secs)) => i64::try_from(secs)
+            .ok()
+            .and_then(Duration::try_seconds)
+            .and_then(|d| Utc::now().checked_add_signed(d)),
+        (Some(_), Some(_)) => {
+            return Err(JsonErrorResponse::new(
+                ErrorCode::InvalidExpiry,
+                "only one of expires_at and expires_in may be specified",
+            ))
+        }
+    };
+
+    match expires_at {
+        Some(at) if at > Utc::now() => Ok(Some(at)),
+        _ => Err(
+            JsonErrorResponse::new(ErrorCode::InvalidExpiry, "expiry must be in the future")
+                .with_field(if expires_at.is_some() {
+                    "expires_at"
+                } else {
+                    "expires_in"
+                }),
+        ),
     }
-    Ok(())
 }
 
-pub fn check_slug_access(
+/// Looks up a paste and checks the edit code given for it. A plaintext edit
+/// code left by an older version is replaced by its hash on the way.
+pub async fn check_slug_access(
     db: &Database,
     slug: &str,
     edit_code: &str,
-) -> Result<(), JsonErrorResponse> {
+) -> Result<SlugRecord, JsonErrorResponse> {
     let record = check_slug_exists(db, slug)?;
-    if record.edit_code == edit_code {
-        Ok(())
-    } else {
-        Err(JsonErrorResponse(
-            StatusCode::FORBIDDEN,
-            "You do not have permission to edit this document".into(),
-        ))
+    let (code, stored) = (edit_code.to_string(), record.edit_code.clone());
+    let matched = spawn_argon2(move || verify_edit_code(&code, &stored)).await;
+
+    match matched {
+        EditCodeMatch::Valid => Ok(record),
+        EditCodeMatch::ValidLegacy => {
+            let code = edit_code.to_string();
+            let hash = spawn_argon2(move || hash_edit_code(&code)).await?;
+            Ok(upgrade_edit_code(db, slug, &record, &hash)?)
+        }
+        EditCodeMatch::Invalid => Err(JsonErrorResponse::new(
+            ErrorCode::Forbidden,
+            "You do not have permission to edit this document",
+        )
+        .with_field("edit_code")),
+    }
+}
+
+/// Follows the alias left behind when a paste was renamed, so links to its old
+/// slug keep working. Slugs that are not aliases are returned unchanged.
+pub fn resolve_slug(db: &Database, slug: &str) -> Result<String, JsonErrorResponse> {
+    match db.resolve_alias(slug)? {
+        Some(record) => Ok(record.display_slug),
+        None => Ok(slug.to_string()),
+    }
+}
+
+/// Header carrying the view password of a protected paste.
+pub const VIEW_PASSWORD_HEADER: &str = "x-view-password";
+
+/// Checks access to a paste protected by a view password. The password is
+/// taken from `view_password` in the body or the `X-View-Password` header,
+/// unless the cookie the frontend sets once it was entered is present.
+pub async fn check_view_access(
+    state: &AppState,
+    record: &SlugRecord,
+    headers: &HeaderMap,
+    body: Option<&ViewAccess>,
+) -> Result<(), JsonErrorResponse> {
+    let Some(ref stored) = record.view_password else {
+        return Ok(());
+    };
+
+    let slug = &record.display_slug;
+    if cookie(headers, &view_cookie_name(slug))
+        .is_some_and(|value| verify_view_cookie(&state.view_cookie_key, slug, stored, value))
+    {
+        return Ok(());
+    }
+
+    let password = body
+        .and_then(|body| body.view_password.as_deref())
+        .or_else(|| {
+            headers
+                .get(VIEW_PASSWORD_HEADER)
+                .and_then(|value| value.to_str().ok())
+        });
+    let Some(password) = password else {
+        return Err(JsonErrorResponse::new(
+            ErrorCode::ViewPasswordRequired,
+            "this paste is protected by a view password",
+        )
+        .with_field("view_password"));
+    };
+    match state.view_passwords.check(slug, password, stored).await {
+        ViewPasswordCheck::Correct => Ok(()),
+        ViewPasswordCheck::Wrong => Err(JsonErrorResponse::new(
+            ErrorCode::Forbidden,
+            "the view password is incorrect",
+        )
+        .with_field("view_password")),
+        ViewPasswordCheck::Throttled => Err(JsonErrorResponse::new(
+            ErrorCode::TooManyAttempts,
+            "too many wrong view passwords were tried for this paste, try again later",
+        )
+        .with_field("view_password")),
     }
 }
 
+/// Looks up a cookie sent with the request.
+pub fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find_map(|(key, value)| (key == name).then_some(value))
+}
+
+/// Refuses to render an encrypted paste, which only the client can read.
+pub fn check_not_encrypted(record: &SlugRecord) -> Result<(), JsonErrorResponse> {
+    if record.encrypted {
+        return Err(JsonErrorResponse::new(
+            ErrorCode::EncryptedPaste,
+            "encrypted pastes can only be fetched raw and decrypted by the client",
+        ));
+    }
+    Ok(())
+}
+
 pub fn check_slug_exists(db: &Database, slug: &str) -> Result<SlugRecord, JsonErrorResponse> {
     match db.get_slug(slug)? {
+        Some(slug_record) if slug_record.is_expired() => Err(JsonErrorResponse::new(
+            ErrorCode::Gone,
+            "the requested slug has expired",
+        )),
         Some(slug_record) => Ok(slug_record),
-        None => Err(JsonErrorResponse(
-            StatusCode::NOT_FOUND,
-            "the requested slug was not found".into(),
+        None => Err(JsonErrorResponse::new(
+            ErrorCode::NotFound,
+            "the requested slug was not found",
         )),
     }
 }
@@ -147,55 +505,350 @@ pub fn check_document_exists(
 ) -> Result<DocumentRecord, JsonErrorResponse> {
     match db.get_document(hash)? {
         Some(doc_record) => Ok(doc_record),
-        None => Err(JsonErrorResponse(
-            StatusCode::NOT_FOUND,
-            "the requested document was not found".into(),
+        None => Err(JsonErrorResponse::new(
+            ErrorCode::NotFound,
+            "the requested document was not found",
         )),
     }
 }
 
+/// Looks up a paste for reading and counts the read, so that burn-after-reading
+/// pastes are handed out at most as many times as requested.
+pub fn check_paste_read(
+    db: &Database,
+    slug: &str,
+    method: &Method,
+) -> Result<(SlugRecord, DocumentRecord), JsonErrorResponse> {
+    loop {
+        let slug_record = check_slug_exists(db, slug)?;
+        let doc_record = check_document_exists(db, &slug_record.document_hash)?;
+
+        if count_read(db, slug, &slug_record, method)? {
+            return Ok((slug_record, doc_record));
+        }
+    }
+}
+
+/// Counts a read like [`consume_read`], except for HEAD requests, which are
+/// answered without the content and so must not use up a read.
+pub fn count_read(
+    db: &Database,
+    slug: &str,
+    slug_record: &SlugRecord,
+    method: &Method,
+) -> Result<bool, Error> {
+    if *method == Method::HEAD {
+        return Ok(true);
+    }
+    consume_read(db, slug, slug_record)
+}
+
 /// Handles the creation of a new paste, receiving paste details as JSON.
 /// Returns a unique identifier for the newly created paste.
 async fn create_paste_handler(
     state: Extension<AppState>,
-    request: extract::Json<CreatePaste>,
+    request: JsonBody<CreatePaste>,
 ) -> Result<Json<CreatePasteResponse>, JsonErrorResponse> {
+    let limits = &state.config.limits;
+    request.validate(limits)?;
+
     if let Some(ref slug) = request.custom_slug {
-        check_slug_format(slug)?;
+        check_slug_not_blocked(&state, slug)?;
 
-        if let Some(_) = state.db.get_slug(slug)? {
-            return Err(JsonErrorResponse(
-                StatusCode::CONFLICT,
-                "specified slug is taken".into(),
-            ));
+        // fail early before hashing the edit code, `create_paste` has the final say
+        let taken = |record: Option<SlugRecord>| record.is_some_and(|r| !r.is_expired());
+        if taken(state.db.get_slug(slug)?) || taken(state.db.resolve_alias(slug)?) {
+            return Err(
+                JsonErrorResponse::new(ErrorCode::SlugTaken, "specified slug is taken")
+                    .with_field("custom_slug"),
+            );
         }
     }
 
-    if let Some(ref edit_code) = request.edit_code {
-        check_edit_code_format(edit_code)?;
-    }
+    let expires_at = check_expiry(request.expires_at, request.expires_in)?;
+
+    let (slug_len, edit_code_len) = (limits.generated_slug_len, limits.generated_edit_code_len);
+    let edit_code = request
+        .edit_code
+        .clone()
+        .unwrap_or_else(|| nanoid!(edit_code_len));
+
+    let secrets = (edit_code.clone(), request.view_password.clone());
+    let (edit_code_hash, view_password_hash) = spawn_argon2(move || {
+        let (edit_code, view_password) = secrets;
+        Ok::<_, Error>((
+            hash_edit_code(&edit_code)?,
+            view_password
+                .as_deref()
+                .map(hash_view_password)
+                .transpose()?,
+        ))
+    })
+    .await?;
 
-    check_document(&request.content)?;
+    let settings = PasteSettings {
+        expires_at,
+        burn_after_reads: request.burn_after_reads,
+        view_password: view_password_hash.as_deref(),
+        encrypted: request.encrypted,
+    };
+
+    if let Some(ref slug) = request.custom_slug {
+        if !create_paste(
+            &state.db,
+            slug,
+            &edit_code_hash,
+            &request.content,
+            &settings,
+        )? {
+            return Err(
+                JsonErrorResponse::new(ErrorCode::SlugTaken, "specified slug is taken")
+                    .with_field("custom_slug"),
+            );
+        }
+        return Ok(Json(CreatePasteResponse {
+            slug: slug.clone(),
+            edit_code,
+        }));
+    }
 
-    let slug = request.custom_slug.clone().unwrap_or(nanoid!(8));
-    let edit_code = request.edit_code.clone().unwrap_or(nanoid!(16));
+    // nobody asked for a generated slug, so one that is taken is just drawn again
+    for _ in 0..GENERATED_SLUG_ATTEMPTS {
+        let slug = nanoid!(slug_len, &SLUG_ALPHABET);
+        if is_reserved_slug(&slug)
+            || state.blocklist.is_blocked(&slug)
+            || state.db.resolve_alias(&slug)?.is_some()
+        {
+            continue;
+        }
+        if create_paste(
+            &state.db,
+            &slug,
+            &edit_code_hash,
+            &request.content,
+            &settings,
+        )? {
+            return Ok(Json(CreatePasteResponse { slug, edit_code }));
+        }
+    }
 
-    create_paste(&state.db, &slug, &edit_code, &request.content)?;
-    Ok(Json(CreatePasteResponse { slug, edit_code }))
+    error!("Gave up generating a slug after {GENERATED_SLUG_ATTEMPTS} attempts");
+    Err(JsonErrorResponse::new(
+        ErrorCode::Internal,
+        "could not generate a free slug",
+    ))
 }
 
+/// The characters generated slugs are made of. Slugs are case-insensitive,
+/// so upper case letters would only add more spellings of the same slug.
+const SLUG_ALPHABET: [char; 36] = [
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's',
+    't', 'u', 'v', 'w', 'x', 'y', 'z', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9',
+];
+
+/// How many generated slugs are tried before creating a paste fails.
+const GENERATED_SLUG_ATTEMPTS: usize = 8;
+
 /// Edits an existing paste identified by a unique ID, updating it with new content provided as JSON.
 /// Returns confirmation of the edit operation.
 async fn edit_paste_handler(
     state: Extension<AppState>,
     slug: extract::Path<String>,
-    request: extract::Json<EditPaste>,
-) -> Result<Json<EditPasteResponse>, JsonErrorResponse> {
-    let slug = slug.as_str();
-    check_slug_access(&state.db, slug, &request.edit_code)?;
+    headers: HeaderMap,
+    request: JsonBody<EditPaste>,
+) -> Result<Response, JsonErrorResponse> {
+    let slug = &resolve_slug(&state.db, slug.as_str())?;
+    let limits = &state.config.limits;
+    request.validate(limits)?;
+    let slug_record = check_slug_access(&state.db, slug, &request.edit_code).await?;
+    if slug_record.encrypted {
+        validate_envelope(&request.content)?;
+    }
+
+    let expires_at = check_expiry(request.expires_at, request.expires_in)?;
+    let base = match check_base_hash(
+        &headers,
+        request.base_hash.as_deref(),
+        &slug_record.document_hash,
+    )? {
+        EditBase::Any => None,
+        EditBase::Document(hash) => Some(hash),
+        EditBase::Unmatched => return Ok(stale_edit_response(slug_record.document_hash)),
+    };
 
-    create_paste(&state.db, slug, &request.edit_code, &request.content)?;
-    Ok(Json(EditPasteResponse {}))
+    let outcome = edit_paste(
+        &state.db,
+        slug,
+        &slug_record,
+        base.as_ref(),
+        &request.content,
+        request.message.as_deref(),
+        expires_at,
+    )?;
+
+    let current = match outcome {
+        EditOutcome::Edited(hash) => {
+            return Ok(Json(EditPasteResponse {
+                hash: hash.to_string(),
+                merged: None,
+            })
+            .into_response())
+        }
+        EditOutcome::Stale(current) => current,
+        EditOutcome::Missing => return Err(missing_paste()),
+    };
+
+    // the edit was made against an older document, try to bring it forward,
+    // unless it is ciphertext which can't be merged line by line
+    let Some(base) = base.filter(|_| !slug_record.encrypted) else {
+        return Ok(stale_edit_response(current));
+    };
+    let merged = match merge_stale_edit(
+        &state.db,
+        slug,
+        &slug_record,
+        &base,
+        &current,
+        &request.content,
+    )? {
+        Some(Merge::Clean(merged)) => merged,
+        Some(Merge::Conflicted { content, conflicts }) => {
+            let error = JsonErrorResponse::new(
+                ErrorCode::MergeConflict,
+                "the edit conflicts with changes made since the base revision",
+            )
+            .with_detail("current_hash", current.to_string())
+            .with_detail("conflicts", conflicts)
+            // the merge with conflict markers around the clashing lines
+            .with_detail("content", content);
+            return Ok(([(header::ETAG, format!("\"{current}\""))], error).into_response());
+        }
+        None => return Ok(stale_edit_response(current)),
+    };
+    validate_document(&merged, limits)?;
+
+    let outcome = edit_paste(
+        &state.db,
+        slug,
+        &slug_record,
+        Some(&current),
+        &merged,
+        request.message.as_deref(),
+        expires_at,
+    )?;
+
+    match outcome {
+        EditOutcome::Edited(hash) => Ok(Json(EditPasteResponse {
+            hash: hash.to_string(),
+            merged: Some(merged),
+        })
+        .into_response()),
+        // edited yet again while merging, let the client start over from the latest
+        EditOutcome::Stale(current) => Ok(stale_edit_response(current)),
+        EditOutcome::Missing => Err(missing_paste()),
+    }
+}
+
+fn stale_edit_response(current: DocumentHash) -> Response {
+    let error = JsonErrorResponse::new(
+        ErrorCode::StaleBase,
+        "the paste was edited since the base revision",
+    )
+    .with_detail("current_hash", current.to_string());
+    ([(header::ETAG, format!("\"{current}\""))], error).into_response()
+}
+
+fn missing_paste() -> JsonErrorResponse {
+    JsonErrorResponse::new(ErrorCode::NotFound, "the requested slug was not found")
+}
+
+/// The document an edit was based on, as named by [`check_base_hash`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditBase {
+    /// No base was given, so the edit applies to whatever the paste holds.
+    Any,
+    Document(DocumentHash),
+    /// `If-Match` names neither the current document nor a single older one.
+    Unmatched,
+}
+
+/// Resolves the document an edit was based on, given either as an `If-Match`
+/// header or as `base_hash` in the body.
+///
+/// `If-Match` accepts the ETag of any representation of the paste, since they
+/// all start with the document hash. It may list several tags, and matches if
+/// any strong one names `current`; weak tags never match. A single tag naming
+/// an older document makes that document the base, so the edit can be merged.
+/// `If-Match: *` only asks for the paste to exist, which is already checked,
+/// so it imposes no base.
+pub fn check_base_hash(
+    headers: &HeaderMap,
+    base_hash: Option<&str>,
+    current: &DocumentHash,
+) -> Result<EditBase, JsonErrorResponse> {
+    fn unquote(tag: &str) -> Option<&str> {
+        tag.strip_prefix('"')?.strip_suffix('"')
+    }
+    let invalid = |what: &str| {
+        JsonErrorResponse::new(
+            ErrorCode::InvalidHash,
+            format!("{what} must be the hex encoded hash of a document"),
+        )
+        .with_field(what)
+    };
+
+    let mut tags = Vec::new();
+    for value in headers.get_all(header::IF_MATCH) {
+        let value = value.to_str().map_err(|_| invalid("If-Match"))?;
+        tags.extend(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty()),
+        );
+    }
+    if headers.contains_key(header::IF_MATCH) && tags.is_empty() {
+        return Err(invalid("If-Match"));
+    }
+
+    let from_header = if tags.is_empty() || tags.contains(&"*") {
+        EditBase::Any
+    } else {
+        let mut strong = Vec::new();
+        for tag in &tags {
+            let (weak, tag) = match tag.strip_prefix("W/") {
+                Some(tag) => (true, tag),
+                None => (false, *tag),
+            };
+            let tag = unquote(tag).ok_or_else(|| invalid("If-Match"))?;
+            // tags that are not document hashes are well formed, they just never match
+            if !weak {
+                strong.push(tag.split('.').next().unwrap_or_default().parse().ok());
+            }
+        }
+        match strong[..] {
+            _ if strong.contains(&Some(*current)) => EditBase::Document(*current),
+            [Some(base)] if tags.len() == 1 => EditBase::Document(base),
+            _ => EditBase::Unmatched,
+        }
+    };
+
+    let from_body = base_hash
+        .map(|hash| {
+            hash.parse::<DocumentHash>()
+                .map_err(|_| invalid("base_hash"))
+        })
+        .transpose()?;
+
+    match (from_header, from_body) {
+        (EditBase::Unmatched, _) => Ok(EditBase::Unmatched),
+        (EditBase::Document(header), Some(body)) if header != body => Err(JsonErrorResponse::new(
+            ErrorCode::InvalidHash,
+            "If-Match and base_hash name different documents",
+        )
+        .with_field("base_hash")),
+        (header, body) => Ok(body.map_or(header, EditBase::Document)),
+    }
 }
 
 /// Deletes a specific paste identified by a unique ID.
@@ -203,12 +856,13 @@ async fn edit_paste_handler(
 async fn delete_paste_handler(
     state: Extension<AppState>,
     slug: extract::Path<String>,
-    request: extract::Json<DeletePaste>,
+    request: JsonBody<DeletePaste>,
 ) -> Result<Json<DeletePasteResponse>, JsonErrorResponse> {
-    let slug = slug.as_str();
-    check_slug_access(&state.db, slug, &request.edit_code)?;
+    let slug = &resolve_slug(&state.db, slug.as_str())?;
+    check_slug_access(&state.db, slug, &request.edit_code).await?;
 
     state.db.remove_slug(slug)?;
+    state.db.remove_revisions(slug)?;
     Ok(Json(DeletePasteResponse {}))
 }
 
@@ -216,40 +870,455 @@ async fn delete_paste_handler(
 /// Returns the paste's content and metadata.
 async fn get_paste_handler(
     state: Extension<AppState>,
+    method: Method,
     slug: extract::Path<String>,
-) -> Result<Json<GetPasteResponse>, JsonErrorResponse> {
-    let slug = slug.as_str();
+    headers: HeaderMap,
+    body: Option<JsonBody<ViewAccess>>,
+) -> Result<Response, JsonErrorResponse> {
+    let slug = &resolve_slug(&state.db, slug.as_str())?;
 
     let slug_record = check_slug_exists(&state.db, slug)?;
-    let doc_record = check_document_exists(&state.db, &slug_record.document_hash)?;
+    check_view_access(&state, &slug_record, &headers, body.as_deref()).await?;
+    let validator = json_validator(&slug_record);
+    if validator.is_fresh(&headers) {
+        return Ok(validator.not_modified());
+    }
+
+    let (slug_record, doc_record) = check_paste_read(&state.db, slug, &method)?;
 
-    Ok(Json(GetPasteResponse {
+    Ok(json_validator(&slug_record).apply(Json(GetPasteResponse {
+        slug: slug_record.display_slug.clone(),
         contents: doc_record.content,
         created: doc_record.created,
-    }))
+        expires_at: slug_record.expires_at,
+        reads_remaining: slug_record.reads_remaining.map(|n| n.saturating_sub(1)),
+        encrypted: slug_record.encrypted,
+    })))
+}
+
+/// The JSON view of a paste also carries its slug and expiry, so those go into the ETag too.
+fn json_validator(slug_record: &SlugRecord) -> Validator {
+    let variant = match slug_record.expires_at {
+        Some(at) => format!("json-{}-{}", slug_record.display_slug, at.timestamp()),
+        None => format!("json-{}", slug_record.display_slug),
+    };
+    Validator::new(
+        &slug_record.document_hash,
+        Some(&variant),
+        CachePolicy::for_paste(slug_record),
+    )
 }
 
 /// Retrieves the HTML-rendered content of a specific paste by its unique ID.
 /// Useful for displaying formatted paste content in a web interface.
 async fn get_paste_html_handler(
     state: Extension<AppState>,
+    method: Method,
     slug: extract::Path<String>,
-) -> Result<Json<GetPasteHtmlResponse>, JsonErrorResponse> {
-    let slug = slug.as_str();
+    headers: HeaderMap,
+    body: Option<JsonBody<ViewAccess>>,
+) -> Result<Response, JsonErrorResponse> {
+    let slug = &resolve_slug(&state.db, slug.as_str())?;
+
+    let html_validator = |record: &SlugRecord| {
+        Validator::new(
+            &record.document_hash,
+            Some("html"),
+            CachePolicy::for_paste(record),
+        )
+    };
 
     let slug_record = check_slug_exists(&state.db, slug)?;
-    let doc_record = check_document_exists(&state.db, &slug_record.document_hash)?;
+    check_view_access(&state, &slug_record, &headers, body.as_deref()).await?;
+    check_not_encrypted(&slug_record)?;
+    let validator = html_validator(&slug_record);
+    if validator.is_fresh(&headers) {
+        return Ok(validator.not_modified());
+    }
+
+    let (slug_record, doc_record) = check_paste_read(&state.db, slug, &method)?;
 
-    let html = markdown_to_html_pretty(&doc_record.content);
+    let options = RenderOptions::Highlighted {
+        theme: DEFAULT_THEME.into(),
+    };
+    let html = state
+        .render_cache
+        .get_or_render(slug_record.document_hash, &options, || {
+            options.render(&state.highlighter, &doc_record.content)
+        })
+        .map(|html| html.to_string())
+        .unwrap_or_default();
 
-    Ok(Json(GetPasteHtmlResponse { html }))
+    Ok(html_validator(&slug_record).apply(Json(GetPasteHtmlResponse { html })))
     // Ok(MarkdownPreview { title: String::from("Markdown Document"), content: html })
 }
 
+/// Retrieves the content of a specific paste as plain text, without any JSON wrapping.
+/// Shared with the frontend so `/p/:slug/raw` behaves exactly like the API route.
+pub async fn get_paste_raw_handler(
+    state: Extension<AppState>,
+    method: Method,
+    slug: extract::Path<String>,
+    headers: HeaderMap,
+    body: Option<JsonBody<ViewAccess>>,
+) -> Result<Response, JsonErrorResponse> {
+    let slug = &resolve_slug(&state.db, slug.as_str())?;
+
+    let raw_validator = |record: &SlugRecord| {
+        Validator::new(&record.document_hash, None, CachePolicy::for_paste(record))
+    };
+
+    let slug_record = check_slug_exists(&state.db, slug)?;
+    check_view_access(&state, &slug_record, &headers, body.as_deref()).await?;
+    let validator = raw_validator(&slug_record);
+    if validator.is_fresh(&headers) {
+        return Ok(validator.not_modified());
+    }
+
+    let (slug_record, doc_record) = check_paste_read(&state.db, slug, &method)?;
+
+    Ok(raw_validator(&slug_record).apply(raw_document_response(doc_record)))
+}
+
+/// Builds a `text/plain` response holding the content of a document and the
+/// time it was created.
+pub fn raw_document_response(doc_record: DocumentRecord) -> Response {
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                "text/plain; charset=utf-8".to_string(),
+            ),
+            (header::CONTENT_LENGTH, doc_record.content.len().to_string()),
+            (header::LAST_MODIFIED, http_date(doc_record.created)),
+        ],
+        doc_record.content,
+    )
+        .into_response()
+}
+
+/// Lists the revisions of a specific paste, oldest first.
+/// Returns the hash, timestamp and edit message of every revision.
+async fn get_revisions_handler(
+    state: Extension<AppState>,
+    slug: extract::Path<String>,
+    headers: HeaderMap,
+    body: Option<JsonBody<ViewAccess>>,
+) -> Result<Json<GetRevisionsResponse>, JsonErrorResponse> {
+    let slug = &resolve_slug(&state.db, slug.as_str())?;
+
+    let slug_record = check_slug_exists(&state.db, slug)?;
+    check_view_access(&state, &slug_record, &headers, body.as_deref()).await?;
+    let revisions = paste_revisions(&state.db, slug, &slug_record)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, revision)| RevisionSummary {
+            revision: i + 1,
+            hash: revision.document_hash.to_string(),
+            created: revision.created,
+            message: revision.message,
+        })
+        .collect();
+
+    Ok(Json(GetRevisionsResponse { revisions }))
+}
+
+/// Retrieves a specific revision of a paste by its 1-based revision number.
+/// Returns the content of the paste as it was at that revision.
+async fn get_revision_handler(
+    state: Extension<AppState>,
+    method: Method,
+    extract::Path((slug, n)): extract::Path<(String, usize)>,
+    headers: HeaderMap,
+    body: Option<JsonBody<ViewAccess>>,
+) -> Result<Response, JsonErrorResponse> {
+    let slug = resolve_slug(&state.db, &slug)?;
+
+    // reading an old revision counts as a read of a burn-after-reading paste
+    let (slug_record, revision, doc_record) = loop {
+        let slug_record = check_slug_exists(&state.db, &slug)?;
+        check_view_access(&state, &slug_record, &headers, body.as_deref()).await?;
+        let mut revisions = paste_revisions(&state.db, &slug, &slug_record)?;
+
+        let revision = match n.checked_sub(1) {
+            Some(i) if i < revisions.len() => revisions.swap_remove(i),
+            _ => {
+                return Err(JsonErrorResponse::new(
+                    ErrorCode::NotFound,
+                    "the requested revision was not found",
+                ))
+            }
+        };
+
+        let validator = revision_validator(&slug_record, &revision, n);
+        if validator.is_fresh(&headers) {
+            return Ok(validator.not_modified());
+        }
+
+        let doc_record = check_document_exists(&state.db, &revision.document_hash)?;
+
+        if count_read(&state.db, &slug, &slug_record, &method)? {
+            break (slug_record, revision, doc_record);
+        }
+    };
+
+    let validator = revision_validator(&slug_record, &revision, n);
+    Ok(validator.apply(Json(GetRevisionResponse {
+        revision: n,
+        hash: revision.document_hash.to_string(),
+        contents: doc_record.content,
+        created: revision.created,
+        message: revision.message,
+    })))
+}
+
+/// A revision's response also carries its number and edit message, so the
+/// ETag is tied to the revision itself rather than just its content.
+fn revision_validator(slug_record: &SlugRecord, revision: &RevisionRecord, n: usize) -> Validator {
+    let variant = format!("rev-{n}-{}", revision.created.timestamp_micros());
+    Validator::new(
+        &revision.document_hash,
+        Some(&variant),
+        CachePolicy::for_revision(slug_record),
+    )
+}
+
+/// Compares two revisions of a paste, named by their document hashes.
+/// Returns the changes as a plain text unified diff.
+async fn get_paste_diff_handler(
+    state: Extension<AppState>,
+    method: Method,
+    slug: extract::Path<String>,
+    query: extract::Query<DiffQuery>,
+    headers: HeaderMap,
+    body: Option<JsonBody<ViewAccess>>,
+) -> Result<Response, JsonErrorResponse> {
+    let (from, to) = check_diff_versions(
+        &state,
+        slug.as_str(),
+        &query,
+        &method,
+        &headers,
+        body.as_deref(),
+    )
+    .await?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        unified_diff(&from, &to),
+    )
+        .into_response())
+}
+
+/// Looks up the versions of a paste named by a diff query, counting the read
+/// of a burn-after-reading paste like any other view of its content.
+pub async fn check_diff_versions(
+    state: &AppState,
+    slug: &str,
+    query: &DiffQuery,
+    method: &Method,
+    headers: &HeaderMap,
+    body: Option<&ViewAccess>,
+) -> Result<(Version, Version), JsonErrorResponse> {
+    let db = &state.db;
+    let parse = |hash: Option<&str>, what: &str| {
+        hash.map(|hash| {
+            hash.parse::<DocumentHash>().map_err(|_| {
+                JsonErrorResponse::new(
+                    ErrorCode::InvalidHash,
+                    format!("{what} must be the hex encoded hash of a document"),
+                )
+                .with_field(what)
+            })
+        })
+        .transpose()
+    };
+    let from = parse(query.from.as_deref(), "from")?;
+    let to = parse(query.to.as_deref(), "to")?;
+    let slug = &resolve_slug(db, slug)?;
+
+    loop {
+        let slug_record = check_slug_exists(db, slug)?;
+        check_view_access(state, &slug_record, headers, body).await?;
+        check_not_encrypted(&slug_record)?;
+        let versions = diff_versions(db, slug, &slug_record, from, to)?.ok_or_else(|| {
+            JsonErrorResponse::new(ErrorCode::NotFound, "the requested revision was not found")
+        })?;
+
+        if count_read(db, slug, &slug_record, method)? {
+            return Ok(versions);
+        }
+    }
+}
+
+/// Moves a paste to a new slug, identified by its current or any earlier slug.
+/// The old slug keeps leading to the paste.
+async fn rename_paste_handler(
+    state: Extension<AppState>,
+    slug: extract::Path<String>,
+    request: JsonBody<RenamePaste>,
+) -> Result<Json<RenamePasteResponse>, JsonErrorResponse> {
+    let slug = &resolve_slug(&state.db, slug.as_str())?;
+    request
+        .validate(&state.config.limits)
+        .map_err(|e| JsonErrorResponse::from(e).with_field("new_slug"))?;
+    check_slug_not_blocked(&state, &request.new_slug).map_err(|e| e.with_field("new_slug"))?;
+    let slug_record = check_slug_access(&state.db, slug, &request.edit_code).await?;
+
+    match rename_paste(&state.db, slug, &slug_record, &request.new_slug)? {
+        RenameOutcome::Renamed(record) => Ok(Json(RenamePasteResponse {
+            slug: record.display_slug,
+        })),
+        RenameOutcome::Taken => Err(JsonErrorResponse::new(
+            ErrorCode::SlugTaken,
+            "specified slug is taken",
+        )
+        .with_field("new_slug")),
+        RenameOutcome::Missing => Err(missing_paste()),
+    }
+}
+
+/// Replaces the edit code of a paste, for when the current one has leaked.
+/// A new code is generated unless one is given.
+async fn change_edit_code_handler(
+    state: Extension<AppState>,
+    slug: extract::Path<String>,
+    request: JsonBody<ChangeEditCode>,
+) -> Result<Json<ChangeEditCodeResponse>, JsonErrorResponse> {
+    let slug = &resolve_slug(&state.db, slug.as_str())?;
+    let limits = &state.config.limits;
+    request
+        .validate(limits)
+        .map_err(|e| JsonErrorResponse::from(e).with_field("new_edit_code"))?;
+    let slug_record = check_slug_access(&state.db, slug, &request.edit_code).await?;
+
+    let edit_code_len = limits.generated_edit_code_len;
+    let edit_code = request
+        .new_edit_code
+        .clone()
+        .unwrap_or_else(|| nanoid!(edit_code_len));
+
+    let code = edit_code.clone();
+    let edit_code_hash = spawn_argon2(move || hash_edit_code(&code)).await?;
+    if !change_edit_code(&state.db, slug, &slug_record, &edit_code_hash)? {
+        return Err(JsonErrorResponse::new(
+            ErrorCode::Forbidden,
+            "the edit code was changed or the paste removed in the meantime",
+        )
+        .with_field("edit_code"));
+    }
+
+    Ok(Json(ChangeEditCodeResponse { edit_code }))
+}
+
+/// Lists the slug patterns on the blocklist.
+async fn get_blocklist_handler(
+    state: Extension<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<GetBlocklistResponse>, JsonErrorResponse> {
+    check_admin(&state, &headers)?;
+
+    let patterns = state
+        .blocklist
+        .entries()
+        .into_iter()
+        .map(|entry| BlockedPattern {
+            pattern: entry.pattern,
+            kind: entry.rule.kind,
+            created: entry.rule.created,
+        })
+        .collect();
+
+    Ok(Json(GetBlocklistResponse { patterns }))
+}
+
+/// Adds a slug pattern to the blocklist. Existing pastes are left alone.
+async fn block_slugs_handler(
+    state: Extension<AppState>,
+    headers: HeaderMap,
+    request: JsonBody<BlockSlugs>,
+) -> Result<Json<BlockedPattern>, JsonErrorResponse> {
+    check_admin(&state, &headers)?;
+
+    let regex = compile_pattern(&request.pattern, request.kind).map_err(|e| {
+        JsonErrorResponse::new(ErrorCode::InvalidPattern, format!("invalid pattern: {e}"))
+            .with_field("pattern")
+    })?;
+    let entry = state
+        .blocklist
+        .insert(&state.db, &request.pattern, request.kind, regex)?;
+
+    Ok(Json(BlockedPattern {
+        pattern: entry.pattern,
+        kind: entry.rule.kind,
+        created: entry.rule.created,
+    }))
+}
+
+/// Removes a slug pattern from the blocklist.
+async fn unblock_slugs_handler(
+    state: Extension<AppState>,
+    headers: HeaderMap,
+    request: JsonBody<UnblockSlugs>,
+) -> Result<Json<UnblockSlugsResponse>, JsonErrorResponse> {
+    check_admin(&state, &headers)?;
+
+    if !state.blocklist.remove(&state.db, &request.pattern)? {
+        return Err(JsonErrorResponse::new(
+            ErrorCode::NotFound,
+            "the pattern is not on the blocklist",
+        )
+        .with_field("pattern"));
+    }
+
+    Ok(Json(UnblockSlugsResponse {}))
+}
+
+/// Runs a garbage collection pass right away, which `rentry-rs gc` cannot do
+/// while the server holds the sled database.
+async fn collect_garbage_handler(
+    state: Extension<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<CollectGarbageResponse>, JsonErrorResponse> {
+    check_admin(&state, &headers)?;
+
+    let db = state.db.clone();
+    let removed =
+        match tokio::task::spawn_blocking(move || collect_garbage(&db, GC_GRACE_PERIOD)).await {
+            Ok(removed) => removed?,
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        };
+
+    Ok(Json(CollectGarbageResponse { removed }))
+}
+
 /// Converts markdown content provided in the request body to HTML.
 /// Returns the rendered HTML content for preview or display purposes.
-async fn render_markdown_handler(_request: extract::Json<RenderMarkdown>) {
-    todo!()
+async fn render_markdown_handler(
+    state: Extension<AppState>,
+    request: JsonBody<RenderMarkdown>,
+) -> Result<Json<RenderMarkdownResponse>, JsonErrorResponse> {
+    request.validate(&state.config.limits)?;
+
+    let theme = request.theme.as_deref().unwrap_or(DEFAULT_THEME);
+    let options = match request.mode {
+        RenderMode::Plain => RenderOptions::Plain,
+        RenderMode::Highlighted => RenderOptions::Highlighted {
+            theme: theme.into(),
+        },
+    };
+
+    let html = options
+        .render(&state.highlighter, &request.content)
+        .ok_or_else(|| {
+            JsonErrorResponse::new(
+                ErrorCode::UnknownTheme,
+                format!("unknown highlighting theme: {theme}"),
+            )
+            .with_field("theme")
+        })?;
+
+    Ok(Json(RenderMarkdownResponse { html }))
 }
 
 /// Represents the input structure for creating a new paste.
@@ -258,6 +1327,12 @@ pub struct CreatePaste {
     pub custom_slug: Option<String>,
     pub edit_code: Option<String>,
     pub content: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub expires_in: Option<u64>, // seconds from now
+    pub burn_after_reads: Option<u32>,
+    pub view_password: Option<String>, // needed to read the paste when set
+    #[serde(default)]
+    pub encrypted: bool, // content is an envelope the client encrypted
 }
 
 /// Represents the response structure for creating a new paste.
@@ -273,13 +1348,17 @@ pub struct CreatePasteResponse {
 pub struct EditPaste {
     pub edit_code: String,
     pub content: String,
-    // Fields to be determined
+    pub message: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub expires_in: Option<u64>,   // seconds from now
+    pub base_hash: Option<String>, // the document the edit was made against
 }
 
 /// Represents the response structure for editing a paste.
 #[derive(Debug, Serialize)]
 pub struct EditPasteResponse {
-    // Fields to be determined
+    hash: String,
+    merged: Option<String>, // the merged content, when the edit was based on an older revision
 }
 
 /// Represents the input structure for deleting a paste.
@@ -295,11 +1374,91 @@ pub struct DeletePasteResponse {
     // Fields to be determined
 }
 
+/// Represents the optional body of a request reading a protected paste.
+#[derive(Debug, Deserialize)]
+pub struct ViewAccess {
+    pub view_password: Option<String>,
+}
+
+/// Represents the input structure for moving a paste to a new slug.
+#[derive(Debug, Deserialize)]
+pub struct RenamePaste {
+    pub edit_code: String,
+    pub new_slug: String,
+}
+
+/// Represents the response structure for a renamed paste.
+#[derive(Debug, Serialize)]
+pub struct RenamePasteResponse {
+    slug: String,
+}
+
+/// Represents the input structure for replacing the edit code of a paste.
+#[derive(Debug, Deserialize)]
+pub struct ChangeEditCode {
+    pub edit_code: String,
+    pub new_edit_code: Option<String>, // generated when not given
+}
+
+/// Represents the response structure for a replaced edit code.
+#[derive(Debug, Serialize)]
+pub struct ChangeEditCodeResponse {
+    edit_code: String,
+}
+
 /// Represents the response structure containing the content and metadata of a requested paste.
 #[derive(Debug, Serialize)]
 pub struct GetPasteResponse {
+    slug: String,
     contents: String,
     created: DateTime<Utc>, // Fields to be determined
+    expires_at: Option<DateTime<Utc>>,
+    reads_remaining: Option<u32>,
+    encrypted: bool,
+}
+
+/// Represents the input structure for adding a pattern to the slug blocklist.
+#[derive(Debug, Deserialize)]
+pub struct BlockSlugs {
+    pub pattern: String,
+    pub kind: PatternKind,
+}
+
+/// Represents the input structure for removing a pattern from the slug blocklist.
+#[derive(Debug, Deserialize)]
+pub struct UnblockSlugs {
+    pub pattern: String,
+}
+
+/// Represents the response structure for unblocking a slug pattern.
+#[derive(Debug, Serialize)]
+pub struct UnblockSlugsResponse {}
+
+/// Represents a pattern on the slug blocklist.
+#[derive(Debug, Serialize)]
+pub struct BlockedPattern {
+    pattern: String,
+    kind: PatternKind,
+    created: DateTime<Utc>,
+}
+
+/// Represents the response structure listing the slug blocklist.
+#[derive(Debug, Serialize)]
+pub struct GetBlocklistResponse {
+    patterns: Vec<BlockedPattern>,
+}
+
+/// Represents the response structure for a garbage collection pass.
+#[derive(Debug, Serialize)]
+pub struct CollectGarbageResponse {
+    removed: usize, // the number of unreferenced documents deleted
+}
+
+/// Represents the query naming the two revisions to compare, by document hash.
+#[derive(Debug, Deserialize)]
+pub struct DiffQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
 }
 
 /// Represents the response structure containing the HTML-rendered content of a requested paste.
@@ -309,14 +1468,51 @@ pub struct GetPasteHtmlResponse {
     // Fields to be determined
 }
 
+/// Represents the response structure listing the revisions of a paste.
+#[derive(Debug, Serialize)]
+pub struct GetRevisionsResponse {
+    revisions: Vec<RevisionSummary>,
+}
+
+/// Represents a single entry of a paste's revision history.
+#[derive(Debug, Serialize)]
+pub struct RevisionSummary {
+    revision: usize,
+    hash: String,
+    created: DateTime<Utc>,
+    message: Option<String>,
+}
+
+/// Represents the response structure containing the content of a specific revision.
+#[derive(Debug, Serialize)]
+pub struct GetRevisionResponse {
+    revision: usize,
+    hash: String,
+    contents: String,
+    created: DateTime<Utc>,
+    message: Option<String>,
+}
+
 /// Represents the input structure for converting markdown to HTML.
 #[derive(Debug, Deserialize)]
 pub struct RenderMarkdown {
-    // Fields to be determined
+    pub content: String,
+    #[serde(default)]
+    pub mode: RenderMode,
+    pub theme: Option<String>, // only used by highlighted rendering
+}
+
+/// Selects how markdown is rendered to HTML.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderMode {
+    #[default]
+    Plain,
+    Highlighted,
 }
 
 /// Represents the response structure containing the HTML-rendered markdown content.
 #[derive(Debug, Serialize)]
 pub struct RenderMarkdownResponse {
-    // Fields to be determined
+    html: String,
 }
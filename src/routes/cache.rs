@@ -0,0 +1,124 @@
+use askama_axum::{IntoResponse, Response};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use chrono::{DateTime, Utc};
+
+use crate::db::{DocumentHash, SlugRecord};
+
+/// Format of the dates sent in HTTP headers.
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// How long clients may reuse a revision without asking again.
+const IMMUTABLE_MAX_AGE_SECS: u64 = 24 * 60 * 60;
+
+/// How long clients and proxies may keep a response before checking back.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CachePolicy {
+    /// Burn-after-reading pastes, which must never be stored.
+    NoStore,
+    /// Pastes that can be edited at any time, revalidated on every use.
+    Mutable,
+    /// Pastes behind a view password, revalidated and kept out of shared caches.
+    Private,
+    /// Pastes that disappear at a fixed time, kept out of shared caches.
+    Expiring(DateTime<Utc>),
+    /// Content that never changes, such as a specific revision of a paste.
+    Immutable,
+}
+
+impl CachePolicy {
+    /// The policy for the current state of a paste.
+    pub fn for_paste(record: &SlugRecord) -> Self {
+        match (record.reads_remaining, record.expires_at) {
+            (Some(_), _) => CachePolicy::NoStore,
+            (None, Some(at)) => CachePolicy::Expiring(at),
+            (None, None) if record.view_password.is_some() => CachePolicy::Private,
+            (None, None) => CachePolicy::Mutable,
+        }
+    }
+
+    /// The policy for a past revision of a paste, which only goes away with the paste itself.
+    pub fn for_revision(record: &SlugRecord) -> Self {
+        match CachePolicy::for_paste(record) {
+            CachePolicy::Mutable => CachePolicy::Immutable,
+            policy => policy,
+        }
+    }
+
+    fn cache_control(&self) -> String {
+        match self {
+            CachePolicy::NoStore => "no-store".into(),
+            CachePolicy::Mutable => "public, no-cache".into(),
+            CachePolicy::Private => "private, no-cache".into(),
+            CachePolicy::Expiring(_) => "private, no-cache".into(),
+            CachePolicy::Immutable => {
+                format!("public, max-age={IMMUTABLE_MAX_AGE_SECS}, immutable")
+            }
+        }
+    }
+}
+
+/// A strong entity tag and caching policy for one representation of a document.
+#[derive(Debug, Clone)]
+pub struct Validator {
+    etag: String,
+    policy: CachePolicy,
+}
+
+impl Validator {
+    /// `variant` tells apart the representations of the same document, as a
+    /// strong ETag must differ between responses with different bodies.
+    pub fn new(hash: &DocumentHash, variant: Option<&str>, policy: CachePolicy) -> Self {
+        let etag = match variant {
+            Some(variant) => format!("\"{hash}.{variant}\""),
+            None => format!("\"{hash}\""),
+        };
+        Self { etag, policy }
+    }
+
+    /// Whether the client already holds this representation, going by `If-None-Match`.
+    pub fn is_fresh(&self, headers: &HeaderMap) -> bool {
+        if self.policy == CachePolicy::NoStore {
+            return false;
+        }
+
+        headers
+            .get_all(header::IF_NONE_MATCH)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == self.etag)
+    }
+
+    /// Answers a request whose `If-None-Match` matched with an empty `304 Not Modified`.
+    pub fn not_modified(self) -> Response {
+        self.apply(StatusCode::NOT_MODIFIED)
+    }
+
+    /// Attaches the ETag and caching headers to a response.
+    pub fn apply(self, response: impl IntoResponse) -> Response {
+        let mut response = response.into_response();
+        let headers = response.headers_mut();
+
+        if self.policy != CachePolicy::NoStore {
+            if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+                headers.insert(header::ETAG, etag);
+            }
+        }
+        if let CachePolicy::Expiring(at) = self.policy {
+            if let Ok(expires) = HeaderValue::from_str(&http_date(at)) {
+                headers.insert(header::EXPIRES, expires);
+            }
+        }
+        if let Ok(cache_control) = HeaderValue::from_str(&self.policy.cache_control()) {
+            headers.insert(header::CACHE_CONTROL, cache_control);
+        }
+
+        response
+    }
+}
+
+/// Formats a timestamp the way HTTP date headers expect.
+pub fn http_date(at: DateTime<Utc>) -> String {
+    at.format(HTTP_DATE_FORMAT).to_string()
+}
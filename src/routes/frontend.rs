@@ -1,7 +1,22 @@
 use askama::Template;
-use axum::{extract, http::Uri, routing::{get, get_service}, Router};
+use askama_axum::{IntoResponse, Response};
+use axum::{extract, http::{header, HeaderMap, Method, StatusCode, Uri}, routing::{get, get_service, post}, Extension, Router};
+use chrono::{Duration, Utc};
+use log::error;
+use serde::Deserialize;
 use tower_http::services::ServeDir;
 
+use crate::{
+    auth::{sign_view_cookie, view_cookie_name, ViewPasswordCheck, VIEW_COOKIE_MAX_AGE_SECS},
+    routes::{
+        api::{check_diff_versions, check_view_access, get_paste_raw_handler, DiffQuery},
+        cache::{CachePolicy, Validator},
+    },
+    errors::Error,
+    services::{side_by_side_diff, DiffRow},
+    state::AppState,
+};
+
 /// Creates and returns a router for frontend-related routes.
 ///
 /// This includes routes for serving HTML content, such as the homepage or
@@ -16,6 +31,9 @@ pub fn frontend_routes() -> Router {
         .route("/", get(index_handler))
         .route("/admin", get(admin_handler))
         .route("/p/:slug", get(paste_handler))
+        .route("/p/:slug/raw", get(get_paste_raw_handler))
+        .route("/p/:slug/diff", get(diff_handler))
+        .route("/p/:slug/unlock", post(unlock_handler))
         .fallback(not_found_handler)
 }
 
@@ -48,6 +66,12 @@ struct NotFoundTemplate {
     address: String,
 }
 
+#[derive(Template)]
+#[template(path="410.html")]
+struct GoneTemplate {
+    address: String,
+}
+
 #[derive(Template)]
 #[template(path="admin.html")]
 struct AdminTemplate {}
@@ -63,6 +87,160 @@ pub struct MarkdownPreview {
     slug: String,
 }
 
-async fn paste_handler(slug: extract::Path<String>) -> MarkdownPreview {
-    MarkdownPreview { slug: slug.0 }
-}
\ No newline at end of file
+async fn paste_handler(
+    state: Extension<AppState>,
+    uri: Uri,
+    headers: HeaderMap,
+    slug: extract::Path<String>,
+) -> Result<Response, StatusCode> {
+    let internal = |e: Error| {
+        error!("Internal server error ({}): {e}", e.kind());
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    let record = state.db.get_slug(slug.as_str()).map_err(internal)?;
+
+    let record = match record {
+        Some(record) if record.is_expired() => {
+            let gone = GoneTemplate { address: uri.to_string() };
+            return Ok((StatusCode::GONE, gone).into_response());
+        }
+        Some(record) => record,
+        None => {
+            // send links to the old slug of a renamed paste over to its new one
+            if let Some(target) = state.db.resolve_alias(slug.as_str()).map_err(internal)? {
+                let location = format!("/p/{}", target.display_slug);
+                return Ok((StatusCode::MOVED_PERMANENTLY, [(header::LOCATION, location)]).into_response());
+            }
+            return Ok(MarkdownPreview { slug: slug.0 }.into_response());
+        }
+    };
+
+    if check_view_access(&state, &record, &headers, None).await.is_err() {
+        return Ok(UnlockTemplate { slug: record.display_slug, failed: false, throttled: false }.into_response());
+    }
+    if record.encrypted {
+        return Ok(EncryptedTemplate { slug: record.display_slug }.into_response());
+    }
+
+    // the page shows the slug, which a rename changes without touching the document
+    let variant = format!("page.{}", record.display_slug);
+    let validator = Validator::new(&record.document_hash, Some(&variant), CachePolicy::for_paste(&record));
+    if validator.is_fresh(&headers) {
+        return Ok(validator.not_modified());
+    }
+
+    Ok(validator.apply(MarkdownPreview { slug: record.display_slug }))
+}
+
+/// Decrypts an encrypted paste in the browser, with the key from the URL fragment.
+#[derive(Template)]
+#[template(path="encrypted.html")]
+struct EncryptedTemplate {
+    slug: String,
+}
+
+#[derive(Template)]
+#[template(path="unlock.html")]
+struct UnlockTemplate {
+    slug: String,
+    failed: bool,
+    throttled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct UnlockForm {
+    password: String,
+}
+
+/// Checks the view password entered for a protected paste and hands out a
+/// signed cookie, so the page and the API requests it makes can read the paste.
+async fn unlock_handler(
+    state: Extension<AppState>,
+    uri: Uri,
+    slug: extract::Path<String>,
+    form: extract::Form<UnlockForm>,
+) -> Result<Response, StatusCode> {
+    let record = state.db.get_slug(slug.as_str()).map_err(|e| {
+        error!("Internal server error ({}): {e}", e.kind());
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let record = match record {
+        Some(record) if !record.is_expired() => record,
+        _ => {
+            let not_found = NotFoundTemplate { address: uri.to_string() };
+            return Ok((StatusCode::NOT_FOUND, not_found).into_response());
+        }
+    };
+    let location = format!("/p/{}", record.display_slug);
+    let Some(ref stored) = record.view_password else {
+        return Ok((StatusCode::SEE_OTHER, [(header::LOCATION, location)]).into_response());
+    };
+
+    match state.view_passwords.check(&record.display_slug, &form.password, stored).await {
+        ViewPasswordCheck::Correct => {}
+        ViewPasswordCheck::Wrong => {
+            let unlock = UnlockTemplate { slug: record.display_slug, failed: true, throttled: false };
+            return Ok((StatusCode::FORBIDDEN, unlock).into_response());
+        }
+        ViewPasswordCheck::Throttled => {
+            let unlock = UnlockTemplate { slug: record.display_slug, failed: false, throttled: true };
+            return Ok((StatusCode::TOO_MANY_REQUESTS, unlock).into_response());
+        }
+    }
+
+    let expires = Utc::now() + Duration::seconds(VIEW_COOKIE_MAX_AGE_SECS);
+    let cookie = format!(
+        "{}={}; Max-Age={VIEW_COOKIE_MAX_AGE_SECS}; Path=/; HttpOnly; SameSite=Strict",
+        view_cookie_name(&record.display_slug),
+        sign_view_cookie(&state.view_cookie_key, &record.display_slug, stored, expires),
+    );
+    Ok((
+        StatusCode::SEE_OTHER,
+        [(header::LOCATION, location), (header::SET_COOKIE, cookie)],
+    )
+        .into_response())
+}
+
+#[derive(Template)]
+#[template(path="diff.html")]
+struct DiffTemplate {
+    slug: String,
+    from: String,
+    to: String,
+    rows: Vec<DiffRow>,
+}
+
+async fn diff_handler(
+    state: Extension<AppState>,
+    uri: Uri,
+    slug: extract::Path<String>,
+    query: extract::Query<DiffQuery>,
+    method: Method,
+    headers: HeaderMap,
+) -> Response {
+    let (from, to) = match check_diff_versions(&state, slug.as_str(), &query, &method, &headers, None).await {
+        Ok(versions) => versions,
+        Err(e) => {
+            let address = uri.to_string();
+            return match e.status() {
+                StatusCode::NOT_FOUND => (StatusCode::NOT_FOUND, NotFoundTemplate { address }).into_response(),
+                StatusCode::GONE => (StatusCode::GONE, GoneTemplate { address }).into_response(),
+                // the paste page asks for the view password
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                    let location = format!("/p/{}", slug.0);
+                    (StatusCode::SEE_OTHER, [(header::LOCATION, location)]).into_response()
+                }
+                status => status.into_response(),
+            };
+        }
+    };
+
+    DiffTemplate {
+        slug: slug.0,
+        from: from.hash.to_string(),
+        to: to.hash.to_string(),
+        rows: side_by_side_diff(&from, &to),
+    }
+    .into_response()
+}
@@ -3,8 +3,12 @@ use axum::Router;
 use crate::routes::{api::api_routes, frontend::frontend_routes};
 
 pub mod api;
+pub mod cache;
 pub mod frontend;
 
+#[cfg(test)]
+mod tests;
+
 /// Configures and returns the global `Router` for the application.
 ///
 /// This function combines all route sub-routers from different modules,
@@ -0,0 +1,1348 @@
+//! Handler tests, run once against every storage backend.
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    Extension, Router,
+};
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+use tower::Service;
+
+use crate::{
+    auth::MAX_WRONG_VIEW_PASSWORDS,
+    config::{Config, DatabaseBackend},
+    db::{DocumentRecord, RevisionRecord, SlugRecord},
+    routes::configure_routes,
+    services::{collect_garbage, hash_legacy_edit_codes, reap_expired, GC_GRACE_PERIOD},
+    state::AppState,
+};
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+struct TestApp {
+    router: Router,
+    state: AppState,
+    _dir: TempDir,
+}
+
+struct TestResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Value,
+}
+
+impl TestApp {
+    fn new(backend: DatabaseBackend) -> Self {
+        Self::with_config(backend, |_| {})
+    }
+
+    /// Starts an app whose config has been adjusted by `configure`.
+    fn with_config(backend: DatabaseBackend, configure: impl FnOnce(&mut Config)) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.database.backend = backend;
+        config.database.path = dir.path().join("database");
+        config.database.cache_capacity_bytes = 1024 * 1024;
+        config.admin_token = Some(ADMIN_TOKEN.to_string());
+        configure(&mut config);
+
+        let state = AppState::new(config);
+        let router = configure_routes().layer(Extension(state.clone()));
+        Self {
+            router,
+            state,
+            _dir: dir,
+        }
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+        headers: &[(&str, &str)],
+    ) -> TestResponse {
+        let mut request = Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .unwrap();
+
+        self.send(request).await
+    }
+
+    /// Posts an HTML form, as the browser does from the frontend pages.
+    async fn post_form(&self, uri: &str, form: &str) -> TestResponse {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(form.to_string()))
+            .unwrap();
+
+        self.send(request).await
+    }
+
+    async fn send(&self, request: Request<Body>) -> TestResponse {
+        let response = self.router.clone().call(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = serde_json::from_slice(&bytes)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()));
+
+        TestResponse {
+            status,
+            headers,
+            body,
+        }
+    }
+
+    async fn get(&self, uri: &str) -> TestResponse {
+        self.request(Method::GET, uri, None, &[]).await
+    }
+
+    async fn post(&self, uri: &str, body: Value) -> TestResponse {
+        self.request(Method::POST, uri, Some(body), &[]).await
+    }
+
+    async fn put(&self, uri: &str, body: Value) -> TestResponse {
+        self.request(Method::PUT, uri, Some(body), &[]).await
+    }
+
+    /// The hash of the document a paste currently holds.
+    fn document_hash(&self, slug: &str) -> String {
+        let record = self.state.db.get_slug(slug).unwrap().unwrap();
+        record.document_hash.to_string()
+    }
+
+    /// Creates a paste and returns its slug and edit code.
+    async fn create(&self, body: Value) -> (String, String) {
+        let response = self.post("/api/pastes", body).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        (
+            response.body["slug"].as_str().unwrap().to_string(),
+            response.body["edit_code"].as_str().unwrap().to_string(),
+        )
+    }
+}
+
+/// Runs each listed test against every backend.
+macro_rules! backend_tests {
+    ($($name:ident),* $(,)?) => {
+        mod sled {
+            $(
+                #[tokio::test]
+                async fn $name() {
+                    super::$name(super::DatabaseBackend::Sled).await;
+                }
+            )*
+        }
+
+        mod sqlite {
+            $(
+                #[tokio::test]
+                async fn $name() {
+                    super::$name(super::DatabaseBackend::Sqlite).await;
+                }
+            )*
+        }
+    };
+}
+
+backend_tests!(
+    pastes_are_created_edited_and_deleted,
+    slugs_are_case_insensitive,
+    burn_after_reading_deletes_on_the_last_read,
+    head_requests_do_not_count_as_reads,
+    expired_pastes_are_reaped,
+    renamed_pastes_keep_their_old_slug,
+    edit_codes_can_be_changed,
+    view_passwords_protect_pastes,
+    blocked_slugs_cannot_be_claimed,
+    orphaned_documents_are_collected,
+    garbage_can_be_collected_by_an_admin,
+    garbage_collection_spares_documents_of_edits_in_progress,
+    edits_swap_the_slug_and_record_the_revision_together,
+    stale_edits_cannot_merge_against_other_pastes,
+    stale_edits_are_merged_or_conflict,
+    malformed_bodies_are_rejected_with_a_code,
+    stale_edits_can_be_sent_as_problem_details,
+    if_match_guards_edits,
+    revisions_can_be_diffed,
+    unchanged_raw_pastes_are_not_modified,
+    unchanged_pages_are_not_modified,
+    generated_slugs_are_drawn_again_when_taken,
+    markdown_is_rendered_plain_or_highlighted,
+    view_passwords_unlock_pages_with_a_cookie,
+    wrong_view_passwords_are_throttled,
+    encrypted_page_modules_are_served,
+    encrypted_pastes_hold_only_envelopes,
+    plaintext_edit_codes_are_hashed_on_use,
+);
+
+async fn pastes_are_created_edited_and_deleted(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    let (slug, edit_code) = app
+        .create(json!({ "custom_slug": "notes", "content": "first" }))
+        .await;
+
+    let paste = app.get("/api/pastes/notes").await;
+    assert_eq!(paste.status, StatusCode::OK);
+    assert_eq!(paste.body["contents"], "first");
+
+    let edit = app
+        .request(
+            Method::PUT,
+            "/api/pastes/notes",
+            Some(json!({ "edit_code": edit_code, "content": "second", "message": "fix" })),
+            &[],
+        )
+        .await;
+    assert_eq!(edit.status, StatusCode::OK, "{}", edit.body);
+
+    let wrong = app
+        .request(
+            Method::PUT,
+            "/api/pastes/notes",
+            Some(json!({ "edit_code": "not-the-code", "content": "third" })),
+            &[],
+        )
+        .await;
+    assert_eq!(wrong.status, StatusCode::FORBIDDEN);
+
+    let revisions = app.get("/api/pastes/notes/revisions").await;
+    let revisions = revisions.body["revisions"].as_array().unwrap().clone();
+    assert_eq!(revisions.len(), 2);
+    assert_eq!(revisions[1]["revision"], 2);
+    assert_eq!(revisions[1]["message"], "fix");
+
+    let first = app.get("/api/pastes/notes/revisions/1").await;
+    assert_eq!(first.body["contents"], "first");
+    let raw = app.get("/api/pastes/notes/raw").await;
+    assert_eq!(raw.body, "second");
+
+    let delete = app
+        .request(
+            Method::DELETE,
+            &format!("/api/pastes/{slug}"),
+            Some(json!({ "edit_code": edit_code })),
+            &[],
+        )
+        .await;
+    assert_eq!(delete.status, StatusCode::OK);
+    assert_eq!(
+        app.get("/api/pastes/notes").await.status,
+        StatusCode::NOT_FOUND
+    );
+    assert!(app.state.db.get_revisions("notes").unwrap().is_empty());
+}
+
+async fn slugs_are_case_insensitive(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    app.create(json!({ "custom_slug": "MyNotes", "content": "mine" }))
+        .await;
+
+    let taken = app
+        .post(
+            "/api/pastes",
+            json!({ "custom_slug": "mynotes", "content": "theirs" }),
+        )
+        .await;
+    assert_eq!(taken.status, StatusCode::CONFLICT);
+
+    let paste = app.get("/api/pastes/MYNOTES").await;
+    assert_eq!(paste.body["contents"], "mine");
+    assert_eq!(paste.body["slug"], "MyNotes");
+}
+
+async fn burn_after_reading_deletes_on_the_last_read(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    let (slug, _) = app
+        .create(json!({ "content": "once or twice", "burn_after_reads": 2 }))
+        .await;
+    let uri = format!("/api/pastes/{slug}");
+
+    let first = app.get(&uri).await;
+    assert_eq!(first.body["reads_remaining"], 1);
+    let second = app.get(&uri).await;
+    assert_eq!(second.body["contents"], "once or twice");
+    assert_eq!(app.get(&uri).await.status, StatusCode::NOT_FOUND);
+}
+
+async fn head_requests_do_not_count_as_reads(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    let (slug, _) = app
+        .create(json!({ "content": "just once", "burn_after_reads": 1 }))
+        .await;
+
+    for uri in [
+        format!("/api/pastes/{slug}"),
+        format!("/api/pastes/{slug}/raw"),
+        format!("/api/pastes/{slug}/html"),
+        format!("/p/{slug}/raw"),
+    ] {
+        let head = app.request(Method::HEAD, &uri, None, &[]).await;
+        assert_eq!(head.status, StatusCode::OK, "{uri}");
+    }
+
+    let uri = format!("/api/pastes/{slug}/raw");
+    assert_eq!(app.get(&uri).await.body, "just once");
+    assert_eq!(app.get(&uri).await.status, StatusCode::NOT_FOUND);
+}
+
+async fn expired_pastes_are_reaped(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    let (slug, _) = app
+        .create(json!({ "content": "short lived", "expires_in": 1 }))
+        .await;
+    let (kept, _) = app
+        .create(json!({ "content": "long lived", "expires_in": 3600 }))
+        .await;
+
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    assert_eq!(reap_expired(&app.state.db).unwrap(), 1);
+
+    assert!(app.state.db.get_slug(&slug).unwrap().is_none());
+    assert_eq!(
+        app.get(&format!("/api/pastes/{kept}")).await.status,
+        StatusCode::OK
+    );
+    assert_eq!(reap_expired(&app.state.db).unwrap(), 0);
+}
+
+async fn renamed_pastes_keep_their_old_slug(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    let (_, edit_code) = app
+        .create(json!({ "custom_slug": "draft", "content": "moving", "expires_in": 3600 }))
+        .await;
+
+    let rename = app
+        .post(
+            "/api/pastes/draft/rename",
+            json!({ "edit_code": edit_code, "new_slug": "Final" }),
+        )
+        .await;
+    assert_eq!(rename.status, StatusCode::OK, "{}", rename.body);
+    assert_eq!(rename.body["slug"], "Final");
+
+    let paste = app.get("/api/pastes/draft").await;
+    assert_eq!(paste.body["slug"], "Final");
+    assert_eq!(paste.body["contents"], "moving");
+    assert_eq!(app.state.db.get_revisions("final").unwrap().len(), 1);
+
+    let taken = app
+        .post(
+            "/api/pastes",
+            json!({ "custom_slug": "draft", "content": "squatting" }),
+        )
+        .await;
+    assert_eq!(taken.status, StatusCode::CONFLICT);
+
+    // renaming back onto its own alias is allowed
+    let back = app
+        .post(
+            "/api/pastes/final/rename",
+            json!({ "edit_code": edit_code, "new_slug": "draft" }),
+        )
+        .await;
+    assert_eq!(back.status, StatusCode::OK, "{}", back.body);
+    assert_eq!(app.get("/api/pastes/final").await.body["slug"], "draft");
+
+    let delete = app
+        .request(
+            Method::DELETE,
+            "/api/pastes/draft",
+            Some(json!({ "edit_code": edit_code })),
+            &[],
+        )
+        .await;
+    assert_eq!(delete.status, StatusCode::OK);
+    app.create(json!({ "custom_slug": "final", "content": "free again" }))
+        .await;
+}
+
+async fn edit_codes_can_be_changed(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    let (_, edit_code) = app
+        .create(json!({ "custom_slug": "locked", "content": "x" }))
+        .await;
+
+    let change = app
+        .post(
+            "/api/pastes/locked/edit-code",
+            json!({ "edit_code": edit_code, "new_edit_code": "brand-new-code" }),
+        )
+        .await;
+    assert_eq!(change.status, StatusCode::OK, "{}", change.body);
+
+    let old = app
+        .post(
+            "/api/pastes/locked/edit-code",
+            json!({ "edit_code": edit_code }),
+        )
+        .await;
+    assert_eq!(old.status, StatusCode::FORBIDDEN);
+
+    let edit = app
+        .request(
+            Method::PUT,
+            "/api/pastes/locked",
+            Some(json!({ "edit_code": "brand-new-code", "content": "y" })),
+            &[],
+        )
+        .await;
+    assert_eq!(edit.status, StatusCode::OK, "{}", edit.body);
+}
+
+async fn view_passwords_protect_pastes(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    app.create(
+        json!({ "custom_slug": "private", "content": "hidden", "view_password": "open sesame" }),
+    )
+    .await;
+
+    let denied = app.get("/api/pastes/private").await;
+    assert_eq!(denied.status, StatusCode::UNAUTHORIZED);
+
+    let allowed = app
+        .request(
+            Method::GET,
+            "/api/pastes/private",
+            None,
+            &[("x-view-password", "open sesame")],
+        )
+        .await;
+    assert_eq!(allowed.status, StatusCode::OK);
+    assert_eq!(allowed.body["contents"], "hidden");
+}
+
+async fn blocked_slugs_cannot_be_claimed(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    let auth = format!("Bearer {ADMIN_TOKEN}");
+    let headers = [("authorization", auth.as_str())];
+
+    let block = app
+        .request(
+            Method::POST,
+            "/api/admin/blocklist",
+            Some(json!({ "pattern": "spam*", "kind": "glob" })),
+            &headers,
+        )
+        .await;
+    assert_eq!(block.status, StatusCode::OK, "{}", block.body);
+
+    let blocked = app
+        .post(
+            "/api/pastes",
+            json!({ "custom_slug": "SpamSpam", "content": "x" }),
+        )
+        .await;
+    assert!(blocked.status.is_client_error(), "{}", blocked.status);
+
+    let list = app
+        .request(Method::GET, "/api/admin/blocklist", None, &headers)
+        .await;
+    assert_eq!(list.body["patterns"][0]["pattern"], "spam*");
+    assert_eq!(app.state.db.iter_block_rules().count(), 1);
+
+    let unblock = app
+        .request(
+            Method::DELETE,
+            "/api/admin/blocklist",
+            Some(json!({ "pattern": "spam*" })),
+            &headers,
+        )
+        .await;
+    assert_eq!(unblock.status, StatusCode::OK);
+    app.create(json!({ "custom_slug": "SpamSpam", "content": "x" }))
+        .await;
+}
+
+async fn orphaned_documents_are_collected(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    let (_, edit_code) = app
+        .create(json!({ "custom_slug": "history", "content": "v1" }))
+        .await;
+    app.request(
+        Method::PUT,
+        "/api/pastes/history",
+        Some(json!({ "edit_code": edit_code, "content": "v2" })),
+        &[],
+    )
+    .await;
+    app.create(json!({ "custom_slug": "other", "content": "kept" }))
+        .await;
+
+    // every revision keeps its document alive
+    assert_eq!(collect_garbage(&app.state.db, Duration::zero()).unwrap(), 0);
+
+    app.request(
+        Method::DELETE,
+        "/api/pastes/history",
+        Some(json!({ "edit_code": edit_code })),
+        &[],
+    )
+    .await;
+    assert_eq!(collect_garbage(&app.state.db, Duration::zero()).unwrap(), 2);
+    assert_eq!(app.state.db.iter_documents().count(), 1);
+    assert_eq!(app.get("/api/pastes/other/raw").await.body, "kept");
+}
+
+async fn garbage_can_be_collected_by_an_admin(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    app.create(json!({ "custom_slug": "kept", "content": "kept" }))
+        .await;
+    let db = &app.state.db;
+    for (content, age) in [("orphan", Duration::hours(1)), ("fresh", Duration::zero())] {
+        db.insert_document(&DocumentRecord {
+            content: content.to_string(),
+            created: Utc::now() - age,
+        })
+        .unwrap();
+    }
+
+    let anonymous = app.request(Method::POST, "/api/admin/gc", None, &[]).await;
+    assert_eq!(anonymous.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(db.iter_documents().count(), 3);
+
+    let auth = format!("Bearer {ADMIN_TOKEN}");
+    let gc = app
+        .request(
+            Method::POST,
+            "/api/admin/gc",
+            None,
+            &[("authorization", auth.as_str())],
+        )
+        .await;
+    assert_eq!(gc.status, StatusCode::OK, "{}", gc.body);
+    // documents written moments ago may belong to an edit in progress
+    assert_eq!(gc.body["removed"], 1);
+    assert_eq!(db.iter_documents().count(), 2);
+    assert_eq!(app.get("/api/pastes/kept/raw").await.body, "kept");
+}
+
+async fn garbage_collection_spares_documents_of_edits_in_progress(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    app.create(json!({ "custom_slug": "racing", "content": "v1" }))
+        .await;
+    let db = &app.state.db;
+
+    // an edit writes its document first...
+    let hash = db
+        .insert_document(&DocumentRecord {
+            content: "v2".to_string(),
+            created: Utc::now(),
+        })
+        .unwrap();
+
+    // ...so a pass that runs before the slug is updated finds it unreferenced
+    assert_eq!(collect_garbage(db, GC_GRACE_PERIOD).unwrap(), 0);
+
+    let current = db.get_slug("racing").unwrap().unwrap();
+    let edited = SlugRecord {
+        document_hash: hash,
+        ..current.clone()
+    };
+    assert!(db
+        .replace_slug_if_unchanged("racing", &current, &edited)
+        .unwrap());
+    assert_eq!(app.get("/api/pastes/racing/raw").await.body, "v2");
+}
+
+async fn edits_swap_the_slug_and_record_the_revision_together(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    app.create(json!({ "custom_slug": "atomic", "content": "v1" }))
+        .await;
+    let db = &app.state.db;
+
+    let hash = db
+        .insert_document(&DocumentRecord {
+            content: "v2".to_string(),
+            created: Utc::now(),
+        })
+        .unwrap();
+    let revision = RevisionRecord {
+        document_hash: hash,
+        created: Utc::now(),
+        message: Some("second".to_string()),
+    };
+    let current = db.get_slug("atomic").unwrap().unwrap();
+    let expires_at = Utc::now() - Duration::minutes(1);
+    let edited = SlugRecord {
+        document_hash: hash,
+        expires_at: Some(expires_at),
+        ..current.clone()
+    };
+
+    // an edit based on a record the paste no longer holds writes nothing at all
+    let stale = SlugRecord {
+        reads_remaining: Some(5),
+        ..current.clone()
+    };
+    assert!(!db
+        .commit_edit("atomic", &stale, &edited, &revision)
+        .unwrap());
+    assert_eq!(db.get_slug("atomic").unwrap().unwrap(), current);
+    assert_eq!(db.get_revisions("atomic").unwrap().len(), 1);
+    assert_eq!(db.iter_expired(Utc::now()).count(), 0);
+
+    assert!(db
+        .commit_edit("Atomic", &current, &edited, &revision)
+        .unwrap());
+    assert_eq!(db.get_slug("atomic").unwrap().unwrap(), edited);
+    let revisions = db.get_revisions("atomic").unwrap();
+    assert_eq!(revisions.len(), 2);
+    assert_eq!(revisions[1], revision);
+    let expired = db
+        .iter_expired(Utc::now())
+        .map(|entry| entry.unwrap().1)
+        .collect::<Vec<_>>();
+    assert_eq!(expired, ["atomic"]);
+}
+
+async fn stale_edits_cannot_merge_against_other_pastes(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    app.create(json!({
+        "custom_slug": "victim",
+        "content": "top secret\n",
+        "view_password": "open sesame",
+    }))
+    .await;
+    let (_, edit_code) = app
+        .create(json!({ "custom_slug": "mine", "content": "mine\n" }))
+        .await;
+
+    let edit = app
+        .put(
+            "/api/pastes/mine",
+            json!({
+                "edit_code": edit_code,
+                "content": "changed\n",
+                "base_hash": app.document_hash("victim"),
+            }),
+        )
+        .await;
+    assert_eq!(edit.status, StatusCode::PRECONDITION_FAILED);
+    assert_eq!(edit.body["current_hash"], app.document_hash("mine"));
+    assert!(!edit.body.to_string().contains("top secret"));
+    assert_eq!(app.get("/api/pastes/mine/raw").await.body, "mine\n");
+}
+
+async fn stale_edits_are_merged_or_conflict(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    let (_, edit_code) = app
+        .create(json!({ "custom_slug": "shared", "content": "a\nb\nc\n" }))
+        .await;
+    let base = app.document_hash("shared");
+
+    let theirs = app
+        .put(
+            "/api/pastes/shared",
+            json!({ "edit_code": edit_code, "content": "a\nb\nC\n" }),
+        )
+        .await;
+    assert_eq!(theirs.status, StatusCode::OK);
+
+    let clean = app
+        .put(
+            "/api/pastes/shared",
+            json!({ "edit_code": edit_code, "content": "A\nb\nc\n", "base_hash": base }),
+        )
+        .await;
+    assert_eq!(clean.status, StatusCode::OK, "{}", clean.body);
+    assert_eq!(clean.body["merged"], "A\nb\nC\n");
+    assert_eq!(app.get("/api/pastes/shared/raw").await.body, "A\nb\nC\n");
+
+    let current = app.document_hash("shared");
+    let conflict = app
+        .put(
+            "/api/pastes/shared",
+            json!({ "edit_code": edit_code, "content": "a\nb\nX\n", "base_hash": base }),
+        )
+        .await;
+    assert_eq!(conflict.status, StatusCode::CONFLICT);
+    assert_eq!(conflict.body["code"], "merge_conflict");
+    assert_eq!(conflict.body["current_hash"], current);
+    assert_eq!(conflict.body["conflicts"], 1);
+    assert_eq!(
+        conflict.body["content"],
+        "A\nb\n<<<<<<< yours\nX\n||||||| base\nc\n=======\nC\n>>>>>>> current\n"
+    );
+    assert_eq!(app.document_hash("shared"), current);
+}
+
+async fn malformed_bodies_are_rejected_with_a_code(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+
+    let missing_field = app
+        .post("/api/pastes", json!({ "custom_slug": "empty" }))
+        .await;
+    assert_eq!(missing_field.status, StatusCode::BAD_REQUEST);
+    assert_eq!(missing_field.body["code"], "invalid_body");
+
+    let no_body = app.request(Method::POST, "/api/pastes", None, &[]).await;
+    assert_eq!(no_body.status, StatusCode::BAD_REQUEST);
+    assert_eq!(no_body.body["code"], "invalid_body");
+
+    let problem = app
+        .request(
+            Method::POST,
+            "/api/pastes",
+            Some(json!(["not", "a", "paste"])),
+            &[("accept", "application/problem+json")],
+        )
+        .await;
+    assert_eq!(problem.status, StatusCode::BAD_REQUEST);
+    assert_eq!(problem.body["type"], "about:blank");
+    assert_eq!(problem.body["status"], 400);
+    assert_eq!(problem.body["code"], "invalid_body");
+}
+
+async fn stale_edits_can_be_sent_as_problem_details(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    let (_, edit_code) = app
+        .create(json!({ "custom_slug": "shared", "content": "a\nb\n" }))
+        .await;
+    app.create(json!({ "custom_slug": "other", "content": "other\n" }))
+        .await;
+    let base = app.document_hash("shared");
+    app.put(
+        "/api/pastes/shared",
+        json!({ "edit_code": edit_code, "content": "a\nB\n" }),
+    )
+    .await;
+    let current = app.document_hash("shared");
+    let accept = [("accept", "application/problem+json")];
+
+    let stale = app
+        .request(
+            Method::PUT,
+            "/api/pastes/shared",
+            Some(json!({
+                "edit_code": edit_code,
+                "content": "a\nc\n",
+                "base_hash": app.document_hash("other"),
+            })),
+            &accept,
+        )
+        .await;
+    assert_eq!(stale.status, StatusCode::PRECONDITION_FAILED);
+    assert_eq!(stale.body["status"], 412);
+    assert_eq!(stale.body["code"], "stale_base");
+    assert_eq!(stale.body["current_hash"], current);
+
+    let conflict = app
+        .request(
+            Method::PUT,
+            "/api/pastes/shared",
+            Some(json!({ "edit_code": edit_code, "content": "a\nc\n", "base_hash": base })),
+            &accept,
+        )
+        .await;
+    assert_eq!(conflict.status, StatusCode::CONFLICT);
+    assert_eq!(conflict.body["status"], 409);
+    assert_eq!(conflict.body["code"], "merge_conflict");
+    assert_eq!(conflict.body["current_hash"], current);
+    assert_eq!(conflict.body["conflicts"], 1);
+}
+
+async fn if_match_guards_edits(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    let (_, edit_code) = app
+        .create(json!({ "custom_slug": "guarded", "content": "v1" }))
+        .await;
+    let edit = |content: &str| json!({ "edit_code": edit_code, "content": content });
+
+    let matching = format!("\"{}\"", app.document_hash("guarded"));
+    let accepted = app
+        .request(
+            Method::PUT,
+            "/api/pastes/guarded",
+            Some(edit("v2")),
+            &[("if-match", &matching)],
+        )
+        .await;
+    assert_eq!(accepted.status, StatusCode::OK, "{}", accepted.body);
+    assert_eq!(app.get("/api/pastes/guarded/raw").await.body, "v2");
+
+    // a document this paste never held can't be merged against
+    let unknown = format!("\"{}\"", "0".repeat(64));
+    let stale = app
+        .request(
+            Method::PUT,
+            "/api/pastes/guarded",
+            Some(edit("v3")),
+            &[("if-match", &unknown)],
+        )
+        .await;
+    assert_eq!(stale.status, StatusCode::PRECONDITION_FAILED);
+    assert_eq!(stale.body["code"], "stale_base");
+    assert_eq!(stale.body["current_hash"], app.document_hash("guarded"));
+    assert_eq!(app.get("/api/pastes/guarded/raw").await.body, "v2");
+
+    // weak tags never match, and a list only matches through the current document
+    let current = app.document_hash("guarded");
+    let older = format!("\"{}\"", "1".repeat(64));
+    for value in [
+        format!("W/\"{current}\""),
+        format!("{unknown}, {older}"),
+        format!("W/\"{current}\", {unknown}"),
+        "\"not-a-hash\"".to_string(),
+    ] {
+        let unmatched = app
+            .request(
+                Method::PUT,
+                "/api/pastes/guarded",
+                Some(edit("v3")),
+                &[("if-match", &value)],
+            )
+            .await;
+        assert_eq!(unmatched.status, StatusCode::PRECONDITION_FAILED, "{value}");
+        assert_eq!(unmatched.body["code"], "stale_base");
+        assert_eq!(unmatched.body["current_hash"], current);
+        assert_eq!(unmatched.headers[header::ETAG], format!("\"{current}\""));
+    }
+    assert_eq!(app.get("/api/pastes/guarded/raw").await.body, "v2");
+
+    let listed = format!("{unknown}, W/\"{current}\", \"{current}.html\"");
+    let accepted = app
+        .request(
+            Method::PUT,
+            "/api/pastes/guarded",
+            Some(edit("v3")),
+            &[("if-match", &listed)],
+        )
+        .await;
+    assert_eq!(accepted.status, StatusCode::OK, "{}", accepted.body);
+    assert_eq!(app.get("/api/pastes/guarded/raw").await.body, "v3");
+
+    for value in [
+        "not-a-hash",
+        "W/not-a-hash",
+        &format!("{unknown}, not-a-hash"),
+        &app.document_hash("guarded"),
+    ] {
+        let invalid = app
+            .request(
+                Method::PUT,
+                "/api/pastes/guarded",
+                Some(edit("v3")),
+                &[("if-match", value)],
+            )
+            .await;
+        assert_eq!(invalid.status, StatusCode::BAD_REQUEST, "{value}");
+        assert_eq!(invalid.body["code"], "invalid_hash");
+        assert_eq!(invalid.body["field"], "If-Match");
+    }
+    assert_eq!(app.get("/api/pastes/guarded/raw").await.body, "v3");
+}
+
+async fn revisions_can_be_diffed(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    let (_, edit_code) = app
+        .create(json!({ "custom_slug": "history", "content": "one\ntwo\n" }))
+        .await;
+    let first = app.document_hash("history");
+    app.put(
+        "/api/pastes/history",
+        json!({ "edit_code": edit_code, "content": "one\nthree\n" }),
+    )
+    .await;
+    let second = app.document_hash("history");
+    app.put(
+        "/api/pastes/history",
+        json!({ "edit_code": edit_code, "content": "one\nthree\nfour\n" }),
+    )
+    .await;
+    app.create(json!({ "custom_slug": "other", "content": "elsewhere\n" }))
+        .await;
+
+    // the latest edit, by default
+    let latest = app.get("/api/pastes/history/diff").await;
+    assert_eq!(latest.status, StatusCode::OK);
+    let latest = latest.body.as_str().unwrap();
+    assert!(latest.contains("+four"), "{latest}");
+    assert!(!latest.contains("two"), "{latest}");
+
+    let between = app
+        .get(&format!(
+            "/api/pastes/history/diff?from={first}&to={second}"
+        ))
+        .await;
+    assert_eq!(between.status, StatusCode::OK);
+    let between = between.body.as_str().unwrap();
+    assert!(
+        between.contains("-two") && between.contains("+three"),
+        "{between}"
+    );
+    assert!(!between.contains("four"), "{between}");
+
+    let foreign = app
+        .get(&format!(
+            "/api/pastes/history/diff?from={}",
+            app.document_hash("other")
+        ))
+        .await;
+    assert_eq!(foreign.status, StatusCode::NOT_FOUND);
+    assert!(!foreign.body.to_string().contains("elsewhere"));
+
+    let invalid = app.get("/api/pastes/history/diff?to=nope").await;
+    assert_eq!(invalid.status, StatusCode::BAD_REQUEST);
+    assert_eq!(invalid.body["field"], "to");
+
+    let page = app
+        .get(&format!("/p/history/diff?from={first}&to={second}"))
+        .await;
+    assert_eq!(page.status, StatusCode::OK);
+    let page = page.body.as_str().unwrap();
+    assert!(page.contains(&format!(
+        "From <code>{first}</code> to <code>{second}</code>"
+    )));
+    assert!(page.contains(r#"<tr class="replace">"#), "{page}");
+    assert!(page.contains(r#"<td class="old">two</td>"#), "{page}");
+    assert!(page.contains(r#"<td class="new">three</td>"#), "{page}");
+
+    let foreign_page = app
+        .get(&format!(
+            "/p/history/diff?from={}",
+            app.document_hash("other")
+        ))
+        .await;
+    assert_eq!(foreign_page.status, StatusCode::NOT_FOUND);
+}
+
+async fn unchanged_raw_pastes_are_not_modified(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    let (_, edit_code) = app
+        .create(json!({ "custom_slug": "plain", "content": "v1" }))
+        .await;
+
+    for uri in ["/api/pastes/plain/raw", "/p/plain/raw"] {
+        let raw = app.get(uri).await;
+        assert_eq!(
+            raw.headers[header::ETAG],
+            format!("\"{}\"", app.document_hash("plain"))
+        );
+        assert_eq!(
+            raw.headers[header::CONTENT_TYPE],
+            "text/plain; charset=utf-8"
+        );
+        let etag = raw.headers[header::ETAG].to_str().unwrap();
+
+        let cached = app
+            .request(Method::GET, uri, None, &[("if-none-match", etag)])
+            .await;
+        assert_eq!(cached.status, StatusCode::NOT_MODIFIED, "{uri}");
+        assert_eq!(cached.body, "");
+    }
+
+    let etag = format!("\"{}\"", app.document_hash("plain"));
+    app.put(
+        "/api/pastes/plain",
+        json!({ "edit_code": edit_code, "content": "v2" }),
+    )
+    .await;
+    let edited = app
+        .request(
+            Method::GET,
+            "/api/pastes/plain/raw",
+            None,
+            &[("if-none-match", &etag)],
+        )
+        .await;
+    assert_eq!(edited.status, StatusCode::OK);
+    assert_eq!(edited.body, "v2");
+}
+
+async fn unchanged_pages_are_not_modified(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    let (_, edit_code) = app
+        .create(json!({ "custom_slug": "page", "content": "v1" }))
+        .await;
+
+    for uri in ["/api/pastes/page", "/api/pastes/page/html", "/p/page"] {
+        let first = app.get(uri).await;
+        assert_eq!(first.status, StatusCode::OK, "{uri}");
+        let etag = first.headers[header::ETAG].to_str().unwrap();
+
+        let cached = app
+            .request(Method::GET, uri, None, &[("if-none-match", etag)])
+            .await;
+        assert_eq!(cached.status, StatusCode::NOT_MODIFIED, "{uri}");
+    }
+
+    // the page names its slug, so a rename must not leave the old page cached
+    let etag = app.get("/p/page").await.headers[header::ETAG].clone();
+    let renamed = app
+        .post(
+            "/api/pastes/page/rename",
+            json!({ "edit_code": edit_code, "new_slug": "moved" }),
+        )
+        .await;
+    assert_eq!(renamed.status, StatusCode::OK, "{}", renamed.body);
+    let moved = app
+        .request(
+            Method::GET,
+            "/p/moved",
+            None,
+            &[("if-none-match", etag.to_str().unwrap())],
+        )
+        .await;
+    assert_eq!(moved.status, StatusCode::OK);
+    assert_ne!(moved.headers[header::ETAG], etag);
+
+    // burn-after-reading pastes are never answered from a cache
+    let (slug, _) = app
+        .create(json!({ "content": "once", "burn_after_reads": 2 }))
+        .await;
+    let uri = format!("/api/pastes/{slug}/raw");
+    let etag = format!("\"{}\"", app.document_hash(&slug));
+    let burned = app
+        .request(Method::GET, &uri, None, &[("if-none-match", &etag)])
+        .await;
+    assert_eq!(burned.status, StatusCode::OK);
+    assert_eq!(burned.body, "once");
+}
+
+async fn generated_slugs_are_drawn_again_when_taken(backend: DatabaseBackend) {
+    // one character leaves 36 slugs, so pastes soon draw taken ones
+    let app = TestApp::with_config(backend, |config| {
+        config.limits.min_slug_len = 1;
+        config.limits.generated_slug_len = 1;
+    });
+
+    let mut slugs = Vec::new();
+    for n in 0..10 {
+        let (slug, _) = app.create(json!({ "content": format!("paste {n}") })).await;
+        assert!(slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()));
+        assert!(!slugs.contains(&slug), "{slug} was handed out twice");
+        slugs.push(slug);
+    }
+
+    let taken = app
+        .post(
+            "/api/pastes",
+            json!({ "custom_slug": slugs[0], "content": "mine" }),
+        )
+        .await;
+    assert_eq!(taken.status, StatusCode::CONFLICT);
+    assert_eq!(taken.body["code"], "slug_taken");
+    assert_eq!(taken.body["field"], "custom_slug");
+}
+
+async fn markdown_is_rendered_plain_or_highlighted(backend: DatabaseBackend) {
+    let app = TestApp::with_config(backend, |config| {
+        config.limits.max_document_bytes = 64;
+    });
+    let markdown = "# Title\n\n```rust\nfn main() {}\n```\n";
+
+    let plain = app
+        .post("/api/markdown/render", json!({ "content": markdown }))
+        .await;
+    assert_eq!(plain.status, StatusCode::OK, "{}", plain.body);
+    let html = plain.body["html"].as_str().unwrap();
+    assert!(html.contains("<h1>Title</h1>"), "{html}");
+    assert!(html.contains("fn main() {}"), "{html}");
+    assert!(!html.contains("style="), "{html}");
+
+    for theme in [None, Some("base16-ocean.dark")] {
+        let highlighted = app
+            .post(
+                "/api/markdown/render",
+                json!({ "content": markdown, "mode": "highlighted", "theme": theme }),
+            )
+            .await;
+        assert_eq!(highlighted.status, StatusCode::OK, "{}", highlighted.body);
+        let html = highlighted.body["html"].as_str().unwrap();
+        assert!(html.contains("<h1>Title</h1>"), "{html}");
+        assert!(html.contains("style=\"color:"), "{html}");
+    }
+
+    let unknown = app
+        .post(
+            "/api/markdown/render",
+            json!({ "content": markdown, "mode": "highlighted", "theme": "no-such-theme" }),
+        )
+        .await;
+    assert_eq!(unknown.status, StatusCode::BAD_REQUEST);
+    assert_eq!(unknown.body["code"], "unknown_theme");
+    assert_eq!(unknown.body["field"], "theme");
+
+    let too_large = app
+        .post(
+            "/api/markdown/render",
+            json!({ "content": "x".repeat(65), "mode": "highlighted" }),
+        )
+        .await;
+    assert_eq!(too_large.status, StatusCode::BAD_REQUEST);
+    assert_eq!(too_large.body["code"], "document_too_large");
+    assert_eq!(too_large.body["field"], "content");
+}
+
+async fn view_passwords_unlock_pages_with_a_cookie(backend: DatabaseBackend) {
+    async fn read(app: &TestApp, slug: &str, cookie: &str) -> TestResponse {
+        let uri = format!("/api/pastes/{slug}/raw");
+        app.request(Method::GET, &uri, None, &[("cookie", cookie)])
+            .await
+    }
+
+    let app = TestApp::new(backend);
+    for slug in ["locked", "other"] {
+        app.create(json!({
+            "custom_slug": slug,
+            "content": format!("{slug} secret"),
+            "view_password": "open sesame",
+        }))
+        .await;
+    }
+
+    let wrong = app.post_form("/p/locked/unlock", "password=not+it").await;
+    assert_eq!(wrong.status, StatusCode::FORBIDDEN);
+    assert!(wrong.headers.get(header::SET_COOKIE).is_none());
+    assert!(wrong
+        .body
+        .as_str()
+        .unwrap()
+        .contains("That password is incorrect."));
+
+    let unlocked = app
+        .post_form("/p/locked/unlock", "password=open+sesame")
+        .await;
+    assert_eq!(unlocked.status, StatusCode::SEE_OTHER);
+    assert_eq!(unlocked.headers[header::LOCATION], "/p/locked");
+    let set_cookie = unlocked.headers[header::SET_COOKIE].to_str().unwrap();
+    assert!(set_cookie.contains("HttpOnly"), "{set_cookie}");
+    let cookie = set_cookie.split(';').next().unwrap().to_string();
+    let (name, value) = cookie.split_once('=').unwrap();
+    assert_eq!(name, "view_locked");
+
+    let granted = read(&app, "locked", &cookie).await;
+    assert_eq!(granted.status, StatusCode::OK);
+    assert_eq!(granted.body, "locked secret");
+
+    // a grant can't be stretched or forged
+    let (expires, mac) = value.split_once('.').unwrap();
+    let later = expires.parse::<i64>().unwrap() + 3600;
+    let flipped = if mac.ends_with('0') { "1" } else { "0" };
+    for tampered in [
+        format!("{later}.{mac}"),
+        format!("{expires}.{}{flipped}", &mac[..mac.len() - 1]),
+    ] {
+        let denied = read(&app, "locked", &format!("view_locked={tampered}")).await;
+        assert_eq!(denied.status, StatusCode::UNAUTHORIZED, "{tampered}");
+    }
+
+    // nor does it unlock another paste with the same password
+    let elsewhere = read(&app, "other", &format!("view_other={value}")).await;
+    assert_eq!(elsewhere.status, StatusCode::UNAUTHORIZED);
+}
+
+async fn wrong_view_passwords_are_throttled(backend: DatabaseBackend) {
+    async fn guess(app: &TestApp, slug: &str, password: &str) -> TestResponse {
+        let uri = format!("/api/pastes/{slug}/raw");
+        app.request(Method::GET, &uri, None, &[("x-view-password", password)])
+            .await
+    }
+
+    let app = TestApp::new(backend);
+    for slug in ["locked", "other"] {
+        app.create(
+            json!({ "custom_slug": slug, "content": "secret", "view_password": "open sesame" }),
+        )
+        .await;
+    }
+
+    // wrong guesses from the API and the unlock form count alike
+    for _ in 0..MAX_WRONG_VIEW_PASSWORDS / 2 {
+        assert_eq!(
+            guess(&app, "locked", "not it").await.status,
+            StatusCode::FORBIDDEN
+        );
+        let wrong = app.post_form("/p/locked/unlock", "password=not+it").await;
+        assert_eq!(wrong.status, StatusCode::FORBIDDEN);
+    }
+
+    let throttled = guess(&app, "locked", "open sesame").await;
+    assert_eq!(throttled.status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(throttled.body["code"], "too_many_attempts");
+    let unlock = app
+        .post_form("/p/locked/unlock", "password=open+sesame")
+        .await;
+    assert_eq!(unlock.status, StatusCode::TOO_MANY_REQUESTS);
+    assert!(unlock.headers.get(header::SET_COOKIE).is_none());
+    assert!(unlock
+        .body
+        .as_str()
+        .unwrap()
+        .contains("Too many wrong passwords were tried."));
+
+    // other pastes still take their password
+    let other = guess(&app, "other", "open sesame").await;
+    assert_eq!(other.status, StatusCode::OK);
+}
+
+async fn encrypted_pastes_hold_only_envelopes(backend: DatabaseBackend) {
+    // a well formed envelope around 16 bytes of ciphertext, told apart by `fill`
+    fn envelope(fill: char) -> Value {
+        json!({
+            "version": 1,
+            "kdf": { "name": "none" },
+            "nonce": "A".repeat(16),
+            "ciphertext": format!("{}A", fill.to_string().repeat(21)),
+        })
+    }
+
+    let app = TestApp::new(backend);
+    let invalid = app
+        .post(
+            "/api/pastes",
+            json!({ "content": "not an envelope", "encrypted": true }),
+        )
+        .await;
+    assert_eq!(invalid.status, StatusCode::BAD_REQUEST);
+    assert_eq!(invalid.body["code"], "invalid_envelope");
+    assert_eq!(invalid.body["field"], "content");
+
+    let (_, edit_code) = app
+        .create(json!({
+            "custom_slug": "sealed",
+            "content": envelope('B').to_string(),
+            "encrypted": true,
+        }))
+        .await;
+
+    // the server can't render or diff what it can't read
+    for uri in ["/api/pastes/sealed/html", "/api/pastes/sealed/diff"] {
+        let refused = app.get(uri).await;
+        assert_eq!(refused.status, StatusCode::UNPROCESSABLE_ENTITY, "{uri}");
+        assert_eq!(refused.body["code"], "encrypted_paste");
+    }
+
+    let plaintext = app
+        .put(
+            "/api/pastes/sealed",
+            json!({ "edit_code": edit_code, "content": "plain text" }),
+        )
+        .await;
+    assert_eq!(plaintext.status, StatusCode::BAD_REQUEST);
+    assert_eq!(plaintext.body["code"], "invalid_envelope");
+    assert_eq!(app.get("/api/pastes/sealed/raw").await.body, envelope('B'));
+
+    let base = app.document_hash("sealed");
+    let edit = app
+        .put(
+            "/api/pastes/sealed",
+            json!({ "edit_code": edit_code, "content": envelope('C').to_string() }),
+        )
+        .await;
+    assert_eq!(edit.status, StatusCode::OK, "{}", edit.body);
+
+    // ciphertext can't be merged line by line, so a stale edit is refused outright
+    let stale = app
+        .put(
+            "/api/pastes/sealed",
+            json!({
+                "edit_code": edit_code,
+                "content": envelope('D').to_string(),
+                "base_hash": base,
+            }),
+        )
+        .await;
+    assert_eq!(stale.status, StatusCode::PRECONDITION_FAILED);
+    assert_eq!(stale.body["code"], "stale_base");
+    assert_eq!(stale.body["current_hash"], app.document_hash("sealed"));
+    assert!(stale.body.get("merged").is_none());
+    assert_eq!(app.get("/api/pastes/sealed/raw").await.body, envelope('C'));
+}
+
+async fn encrypted_page_modules_are_served(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    let template = include_str!("../../templates/encrypted.html");
+    let imports = regex::Regex::new(r"from '(/static/[^']+)'").unwrap();
+
+    let mut served = 0;
+    for module in imports.captures_iter(template) {
+        let response = app.get(&module[1]).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", &module[1]);
+        served += 1;
+    }
+    assert_eq!(served, 2);
+}
+
+/// Stores `edit_code` in plaintext, as versions before edit codes were hashed did.
+fn store_plaintext_edit_code(app: &TestApp, slug: &str, edit_code: &str) {
+    let db = &app.state.db;
+    let record = db.get_slug(slug).unwrap().unwrap();
+    let legacy = SlugRecord {
+        edit_code: edit_code.to_string(),
+        ..record.clone()
+    };
+    assert!(db
+        .replace_slug_if_unchanged(slug, &record, &legacy)
+        .unwrap());
+}
+
+async fn plaintext_edit_codes_are_hashed_on_use(backend: DatabaseBackend) {
+    let app = TestApp::new(backend);
+    let stored = |slug: &str| app.state.db.get_slug(slug).unwrap().unwrap().edit_code;
+    app.create(json!({ "custom_slug": "legacy", "content": "v1" }))
+        .await;
+    store_plaintext_edit_code(&app, "legacy", "old-plain-code");
+
+    let wrong = app
+        .put(
+            "/api/pastes/legacy",
+            json!({ "edit_code": "not-the-code", "content": "v2" }),
+        )
+        .await;
+    assert_eq!(wrong.status, StatusCode::FORBIDDEN);
+    assert_eq!(stored("legacy"), "old-plain-code");
+
+    let edited = app
+        .put(
+            "/api/pastes/legacy",
+            json!({ "edit_code": "old-plain-code", "content": "v2" }),
+        )
+        .await;
+    assert_eq!(edited.status, StatusCode::OK, "{}", edited.body);
+    assert!(stored("legacy").starts_with("$argon2id$"));
+
+    // and the code keeps working once it is hashed
+    let edited = app
+        .put(
+            "/api/pastes/legacy",
+            json!({ "edit_code": "old-plain-code", "content": "v3" }),
+        )
+        .await;
+    assert_eq!(edited.status, StatusCode::OK, "{}", edited.body);
+
+    // `hash-edit-codes` does the same for every paste up front
+    app.create(json!({ "custom_slug": "dormant", "content": "v1" }))
+        .await;
+    store_plaintext_edit_code(&app, "dormant", "dormant-code");
+    assert_eq!(hash_legacy_edit_codes(&app.state.db).unwrap(), 1);
+    assert_eq!(hash_legacy_edit_codes(&app.state.db).unwrap(), 0);
+    assert!(stored("dormant").starts_with("$argon2id$"));
+    let deleted = app
+        .request(
+            Method::DELETE,
+            "/api/pastes/dormant",
+            Some(json!({ "edit_code": "dormant-code" })),
+            &[],
+        )
+        .await;
+    assert_eq!(deleted.status, StatusCode::OK, "{}", deleted.body);
+}
@@ -1,85 +1,913 @@
-use chrono::Utc;
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use lru::LruCache;
 use pulldown_cmark::{CodeBlockKind, Event, Tag, TagEnd};
-use syntect::{highlighting::ThemeSet, html::highlighted_html_for_string, parsing::SyntaxSet};
+use similar::{DiffOp, TextDiff};
+use syntect::{
+    highlighting::{Theme, ThemeSet},
+    html::highlighted_html_for_string,
+    parsing::SyntaxSet,
+};
 
 use crate::{
-    db::{Database, DocumentRecord, SlugRecord},
+    auth::{hash_edit_code, is_legacy_edit_code},
+    db::{Database, DocumentHash, DocumentRecord, MoveOutcome, RevisionRecord, SlugRecord},
     errors::Error,
+    merge::{merge_lines, Merge},
 };
 
+/// How a new paste is shared, besides its slug and edit code.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PasteSettings<'a> {
+    pub expires_at: Option<DateTime<Utc>>,
+    pub burn_after_reads: Option<u32>,
+    /// Hash of the password needed to read the paste, see
+    /// [`hash_view_password`](crate::auth::hash_view_password).
+    pub view_password: Option<&'a str>,
+    /// The content is an envelope that only the client can decrypt.
+    pub encrypted: bool,
+}
+
+/// Creates a new paste under `slug`, editable with the edit code hashed to
+/// `edit_code_hash` by [`hash_edit_code`].
+///
+/// Returns `false` if the slug is already held by a paste that has not expired.
 pub fn create_paste(
     db: &Database,
     slug: &str,
-    edit_code: &str,
+    edit_code_hash: &str,
     content: &str,
-) -> Result<(), Error> {
-    let hash = db.insert_document(&DocumentRecord {
+    settings: &PasteSettings,
+) -> Result<bool, Error> {
+    let created = Utc::now();
+    let doc = DocumentRecord {
         content: content.to_string(),
-        created: Utc::now(),
-    })?;
+        created,
+    };
+    let hash = doc.hash();
 
-    db.insert_slug(
+    db.insert_paste(
         slug,
         &SlugRecord {
             document_hash: hash,
-            edit_code: edit_code.to_string(),
+            edit_code: edit_code_hash.to_string(),
+            expires_at: settings.expires_at,
+            reads_remaining: settings.burn_after_reads,
+            display_slug: slug.to_string(),
+            aliases: Vec::new(),
+            view_password: settings.view_password.map(str::to_string),
+            encrypted: settings.encrypted,
         },
-    )?;
+        &doc,
+        &RevisionRecord {
+            document_hash: hash,
+            created,
+            message: None,
+        },
+    )
+}
+
+/// Replaces the content of an existing paste and records it as a new revision.
+///
+/// The expiry of the paste is only changed when `expires_at` is given. When
+/// `base` is given, the edit only goes through while the paste still holds
+/// that document.
+pub fn edit_paste(
+    db: &Database,
+    slug: &str,
+    record: &SlugRecord,
+    base: Option<&DocumentHash>,
+    content: &str,
+    message: Option<&str>,
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<EditOutcome, Error> {
+    let created = Utc::now();
+    let hash = db.insert_document(&DocumentRecord {
+        content: content.to_string(),
+        created,
+    })?;
+
+    // keep the current document of pastes created before revisions were tracked
+    if db.get_revisions(slug)?.is_empty() {
+        db.insert_revisions(slug, &paste_revisions(db, slug, record)?)?;
+    }
+
+    let revision = RevisionRecord {
+        document_hash: hash,
+        created,
+        message: message.map(str::to_string),
+    };
+
+    // swap against the record the edit was based on, so a concurrent edit is never overwritten
+    let mut current = record.clone();
+    loop {
+        if base.is_some_and(|base| *base != current.document_hash) {
+            return Ok(EditOutcome::Stale(current.document_hash));
+        }
+
+        let edited = SlugRecord {
+            document_hash: hash,
+            expires_at: expires_at.or(current.expires_at),
+            ..current.clone()
+        };
+        if db.commit_edit(slug, &current, &edited, &revision)? {
+            return Ok(EditOutcome::Edited(hash));
+        }
+
+        current = match db.get_slug(slug)? {
+            Some(record) if !record.is_expired() => record,
+            _ => return Ok(EditOutcome::Missing),
+        };
+    }
+}
+
+/// Merges an edit made against `base` into `current`, the document the paste
+/// holds now.
+///
+/// Only a revision of this paste can be the base, as the merge result shows
+/// its content. Returns `None` when `base` is not one, or when either document
+/// is no longer stored.
+pub fn merge_stale_edit(
+    db: &Database,
+    slug: &str,
+    record: &SlugRecord,
+    base: &DocumentHash,
+    current: &DocumentHash,
+    content: &str,
+) -> Result<Option<Merge>, Error> {
+    let revisions = paste_revisions(db, slug, record)?;
+    if !revisions.iter().any(|r| r.document_hash == *base) {
+        return Ok(None);
+    }
+
+    let (Some(base), Some(current)) = (db.get_document(base)?, db.get_document(current)?) else {
+        return Ok(None);
+    };
+
+    Ok(Some(merge_lines(&base.content, content, &current.content)))
+}
+
+/// What became of an edit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditOutcome {
+    /// The paste now holds the document with this hash.
+    Edited(DocumentHash),
+    /// The paste no longer holds the document the edit was based on, but this one.
+    Stale(DocumentHash),
+    /// The paste was deleted or expired while it was being edited.
+    Missing,
+}
+
+/// Moves the paste at `slug` to `new_slug`, keeping `slug` as an alias.
+///
+/// `record` is the paste as it was when its edit code was checked. Concurrent
+/// edits are carried along, but a paste whose edit code changed in the
+/// meantime is treated as gone, as the caller no longer has access to it.
+pub fn rename_paste(
+    db: &Database,
+    slug: &str,
+    record: &SlugRecord,
+    new_slug: &str,
+) -> Result<RenameOutcome, Error> {
+    let mut current = record.clone();
+    loop {
+        match db.move_slug(slug, new_slug, &current)? {
+            MoveOutcome::Moved(renamed) => return Ok(RenameOutcome::Renamed(renamed)),
+            MoveOutcome::Taken => return Ok(RenameOutcome::Taken),
+            MoveOutcome::Changed => {}
+        }
+
+        current = match db.get_slug(slug)? {
+            Some(latest) if !latest.is_expired() && latest.edit_code == record.edit_code => latest,
+            _ => return Ok(RenameOutcome::Missing),
+        };
+    }
+}
+
+/// What became of a rename.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenameOutcome {
+    /// The paste is now stored as this record under its new slug.
+    Renamed(SlugRecord),
+    /// The new slug belongs to another paste.
+    Taken,
+    /// The paste was deleted, expired or had its edit code changed while it was being renamed.
+    Missing,
+}
+
+/// Replaces the edit code of the paste at `slug` with the one hashed to
+/// `edit_code_hash`.
+///
+/// `record` is the paste as it was when its current edit code was checked.
+/// Concurrent edits are carried along, but if the edit code was changed by
+/// someone else first, nothing is written and `false` is returned.
+pub fn change_edit_code(
+    db: &Database,
+    slug: &str,
+    record: &SlugRecord,
+    edit_code_hash: &str,
+) -> Result<bool, Error> {
+    let mut current = record.clone();
+    loop {
+        let changed = SlugRecord {
+            edit_code: edit_code_hash.to_string(),
+            ..current.clone()
+        };
+        if db.replace_slug_if_unchanged(slug, &current, &changed)? {
+            return Ok(true);
+        }
+
+        current = match db.get_slug(slug)? {
+            Some(latest) if !latest.is_expired() && latest.edit_code == record.edit_code => latest,
+            _ => return Ok(false),
+        };
+    }
+}
+
+/// Replaces a plaintext edit code left by an older version with its hash,
+/// `edit_code_hash`.
+///
+/// Returns the record as it is now stored, which is unchanged if the slug was
+/// modified concurrently; the code is then rehashed on its next use instead.
+pub fn upgrade_edit_code(
+    db: &Database,
+    slug: &str,
+    record: &SlugRecord,
+    edit_code_hash: &str,
+) -> Result<SlugRecord, Error> {
+    let upgraded = SlugRecord {
+        edit_code: edit_code_hash.to_string(),
+        ..record.clone()
+    };
+
+    if db.replace_slug_if_unchanged(slug, record, &upgraded)? {
+        Ok(upgraded)
+    } else {
+        Ok(record.clone())
+    }
+}
+
+/// Hashes every edit code still stored in plaintext. Returns the number of slugs updated.
+pub fn hash_legacy_edit_codes(db: &Database) -> Result<usize, Error> {
+    let mut updated = 0;
+
+    for entry in db.iter_slugs() {
+        let (slug, record) = entry?;
+        if !is_legacy_edit_code(&record.edit_code) {
+            continue;
+        }
+
+        let edit_code_hash = hash_edit_code(&record.edit_code)?;
+        if upgrade_edit_code(db, &slug, &record, &edit_code_hash)? != record {
+            updated += 1;
+        }
+    }
+
+    Ok(updated)
+}
+
+/// Counts one read of a paste, deleting burn-after-reading pastes on their last read.
+///
+/// Returns `false` when another reader won the race for `record`, in which
+/// case the slug must be looked up again before its content is handed out.
+pub fn consume_read(db: &Database, slug: &str, record: &SlugRecord) -> Result<bool, Error> {
+    if !db.consume_slug_read(slug, record)? {
+        return Ok(false);
+    }
+
+    if record.reads_remaining.is_some_and(|n| n <= 1) {
+        db.remove_revisions(slug)?;
+    }
+
+    Ok(true)
+}
+
+/// Deletes every paste whose expiry time has passed. Returns the number of pastes deleted.
+///
+/// Expiry entries left behind by edits and deletions are dropped along the way.
+pub fn reap_expired(db: &Database) -> Result<usize, Error> {
+    let mut reaped = 0;
+
+    for entry in db.iter_expired(Utc::now()) {
+        let (at, slug) = entry?;
+
+        if let Some(record) = db.get_slug(&slug)? {
+            if expires_at_millis(&record) == Some(at.timestamp_millis()) {
+                if !db.remove_slug_if_unchanged(&slug, &record)? {
+                    // the paste changed under us, look at it again on the next pass
+                    continue;
+                }
+                db.remove_revisions(&slug)?;
+                reaped += 1;
+            }
+        }
+
+        db.remove_expiry(at, &slug)?;
+    }
+
+    Ok(reaped)
+}
+
+fn expires_at_millis(record: &SlugRecord) -> Option<i64> {
+    record.expires_at.map(|at| at.timestamp_millis())
+}
+
+/// Returns the revision history of a paste, oldest first.
+///
+/// Pastes created before revisions were tracked have no history of their own,
+/// so their current document is reported as the only revision.
+pub fn paste_revisions(
+    db: &Database,
+    slug: &str,
+    record: &SlugRecord,
+) -> Result<Vec<RevisionRecord>, Error> {
+    let revisions = db.get_revisions(slug)?;
+    if !revisions.is_empty() {
+        return Ok(revisions);
+    }
+
+    let created = db
+        .get_document(&record.document_hash)?
+        .map(|doc| doc.created)
+        .unwrap_or_else(Utc::now);
+
+    Ok(vec![RevisionRecord {
+        document_hash: record.document_hash,
+        created,
+        message: None,
+    }])
+}
+
+/// A stored version of a paste.
+#[derive(Debug, Clone)]
+pub struct Version {
+    pub hash: DocumentHash,
+    pub document: DocumentRecord,
+}
+
+/// Looks up the two versions of a paste to compare.
+///
+/// `to` defaults to the current document and `from` to the revision before
+/// `to`. Only documents that are revisions of this paste can be named, so a
+/// diff cannot be used to read arbitrary documents. Returns `None` when either
+/// is not a revision of the paste.
+pub fn diff_versions(
+    db: &Database,
+    slug: &str,
+    record: &SlugRecord,
+    from: Option<DocumentHash>,
+    to: Option<DocumentHash>,
+) -> Result<Option<(Version, Version)>, Error> {
+    let revisions = paste_revisions(db, slug, record)?;
+    let position = |hash: &DocumentHash| revisions.iter().rposition(|r| r.document_hash == *hash);
+
+    let to = to.unwrap_or(record.document_hash);
+    let Some(to_position) = position(&to) else {
+        return Ok(None);
+    };
+    let from = match from {
+        Some(from) if position(&from).is_none() => return Ok(None),
+        Some(from) => from,
+        None => revisions[to_position.saturating_sub(1)].document_hash,
+    };
+
+    let version = |hash: DocumentHash| -> Result<Option<Version>, Error> {
+        Ok(db
+            .get_document(&hash)?
+            .map(|document| Version { hash, document }))
+    };
+
+    match (version(from)?, version(to)?) {
+        (Some(from), Some(to)) => Ok(Some((from, to))),
+        _ => Ok(None),
+    }
+}
+
+/// Renders the changes between two versions as a unified diff.
+pub fn unified_diff(from: &Version, to: &Version) -> String {
+    TextDiff::from_lines(&from.document.content, &to.document.content)
+        .unified_diff()
+        .context_radius(3)
+        .header(&from.hash.to_string(), &to.hash.to_string())
+        .to_string()
+}
+
+/// One row of a side-by-side diff. A side is `None` where a line only exists
+/// on the other side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffRow {
+    pub kind: DiffRowKind,
+    pub old: Option<DiffLine>,
+    pub new: Option<DiffLine>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffLine {
+    pub number: usize,
+    pub text: String,
+}
 
-    Ok(())
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffRowKind {
+    Equal,
+    Delete,
+    Insert,
+    Replace,
+}
+
+impl DiffRowKind {
+    /// The CSS class rows of this kind are rendered with.
+    pub fn class(&self) -> &'static str {
+        match self {
+            DiffRowKind::Equal => "equal",
+            DiffRowKind::Delete => "delete",
+            DiffRowKind::Insert => "insert",
+            DiffRowKind::Replace => "replace",
+        }
+    }
 }
 
+/// Lines up the changes between two versions for a side-by-side view.
+///
+/// Replaced hunks are paired line by line, with the shorter side padded.
+pub fn side_by_side_diff(from: &Version, to: &Version) -> Vec<DiffRow> {
+    let diff = TextDiff::from_lines(&from.document.content, &to.document.content);
+    let old: Vec<&str> = diff.old_slices().to_vec();
+    let new: Vec<&str> = diff.new_slices().to_vec();
+
+    let line = |lines: &[&str], index: usize| DiffLine {
+        number: index + 1,
+        text: lines[index].trim_end_matches(['\r', '\n']).to_string(),
+    };
+
+    let mut rows = Vec::new();
+    for op in diff.ops() {
+        let (kind, old_range, new_range) = match *op {
+            DiffOp::Equal {
+                old_index,
+                new_index,
+                len,
+            } => (
+                DiffRowKind::Equal,
+                old_index..old_index + len,
+                new_index..new_index + len,
+            ),
+            DiffOp::Delete {
+                old_index,
+                old_len,
+                new_index,
+            } => (
+                DiffRowKind::Delete,
+                old_index..old_index + old_len,
+                new_index..new_index,
+            ),
+            DiffOp::Insert {
+                old_index,
+                new_index,
+                new_len,
+            } => (
+                DiffRowKind::Insert,
+                old_index..old_index,
+                new_index..new_index + new_len,
+            ),
+            DiffOp::Replace {
+                old_index,
+                old_len,
+                new_index,
+                new_len,
+            } => (
+                DiffRowKind::Replace,
+                old_index..old_index + old_len,
+                new_index..new_index + new_len,
+            ),
+        };
+
+        for n in 0..old_range.len().max(new_range.len()) {
+            let old_index = old_range.start + n;
+            let new_index = new_range.start + n;
+            rows.push(DiffRow {
+                kind,
+                old: old_range
+                    .contains(&old_index)
+                    .then(|| line(&old, old_index)),
+                new: new_range
+                    .contains(&new_index)
+                    .then(|| line(&new, new_index)),
+            });
+        }
+    }
+
+    rows
+}
+
+/// How long a new document is safe from [`collect_garbage`].
+///
+/// Creating or editing a paste writes its document before the slug that
+/// references it, so a pass that marks slugs in between sees the document as
+/// unreachable. It only has to outlast that gap.
+pub const GC_GRACE_PERIOD: Duration = Duration::minutes(10);
+
+/// Removes every document that is no longer referenced by a slug or a revision.
+///
+/// This is a mark-and-sweep pass: all hashes reachable from the `slugs` and
+/// `revisions` trees are collected first, then unreachable documents are
+/// removed. Documents created less than `grace` before the pass started are
+/// never swept, so a paste that is being created or edited concurrently cannot
+/// lose its document before its slug is written. Returns the number of
+/// documents removed.
+pub fn collect_garbage(db: &Database, grace: Duration) -> Result<usize, Error> {
+    let cutoff = Utc::now() - grace;
+    let mut reachable = HashSet::new();
+
+    for entry in db.iter_slugs() {
+        let (_, record) = entry?;
+        reachable.insert(record.document_hash);
+    }
+
+    for entry in db.iter_revisions() {
+        let (_, revisions) = entry?;
+        reachable.extend(revisions.iter().map(|r| r.document_hash));
+    }
+
+    // pastes set aside by the slug normalization migration are kept until an admin deals with them
+    for entry in db.iter_slug_collisions() {
+        let (_, collision) = entry?;
+        reachable.insert(collision.record.document_hash);
+        reachable.extend(collision.revisions.iter().map(|r| r.document_hash));
+    }
+
+    let mut removed = 0;
+    for entry in db.iter_documents() {
+        let (hash, doc) = entry?;
+        if doc.created < cutoff
+            && !reachable.contains(&hash)
+            && db.remove_document_if_unchanged(&hash, &doc)?
+        {
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Renders markdown to sanitized HTML.
 pub fn markdown_to_html(markdown_src: &str) -> String {
+    render_markdown(markdown_src, None)
+}
+
+/// The syntax highlighting theme used when none is requested.
+pub const DEFAULT_THEME: &str = "InspiredGitHub";
+
+/// The syntax definitions and themes used for highlighting.
+///
+/// Loading these is expensive, so they are loaded once and shared through `AppState`.
+#[derive(Debug)]
+pub struct Highlighter {
+    syntaxes: SyntaxSet,
+    themes: ThemeSet,
+}
+
+impl Highlighter {
+    pub fn load() -> Self {
+        Self {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            themes: ThemeSet::load_defaults(),
+        }
+    }
+}
+
+/// Renders markdown to sanitized HTML with syntax highlighted code blocks.
+///
+/// Returns `None` if `theme_name` is not a known highlighting theme.
+pub fn markdown_to_html_pretty(
+    highlighter: &Highlighter,
+    markdown_src: &str,
+    theme_name: &str,
+) -> Option<String> {
+    let theme = highlighter.themes.themes.get(theme_name)?;
+    Some(render_markdown(
+        markdown_src,
+        Some((&highlighter.syntaxes, theme)),
+    ))
+}
+
+/// Selects how markdown is rendered to HTML.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RenderOptions {
+    Plain,
+    Highlighted { theme: String },
+}
+
+impl RenderOptions {
+    /// Renders markdown with these options. Returns `None` if the theme is unknown.
+    pub fn render(&self, highlighter: &Highlighter, markdown_src: &str) -> Option<String> {
+        match self {
+            RenderOptions::Plain => Some(markdown_to_html(markdown_src)),
+            RenderOptions::Highlighted { theme } => {
+                markdown_to_html_pretty(highlighter, markdown_src, theme)
+            }
+        }
+    }
+}
+
+/// A bounded cache of rendered HTML.
+///
+/// Documents are content-addressed, so the HTML rendered for a `DocumentHash`
+/// with the same options never goes stale and entries are only ever evicted,
+/// least recently used first, once the cached HTML exceeds `capacity_bytes`.
+#[derive(Debug, Clone)]
+pub struct RenderCache {
+    inner: Arc<Mutex<RenderCacheInner>>,
+}
+
+#[derive(Debug)]
+struct RenderCacheInner {
+    entries: LruCache<(DocumentHash, RenderOptions), Arc<str>>,
+    size_bytes: usize,
+    capacity_bytes: usize,
+}
+
+impl RenderCache {
+    pub fn new(capacity_bytes: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(RenderCacheInner {
+                entries: LruCache::unbounded(),
+                size_bytes: 0,
+                capacity_bytes,
+            })),
+        }
+    }
+
+    /// Returns the cached HTML for a document, rendering and caching it on a miss.
+    ///
+    /// Rendering happens outside the lock, so a slow render does not hold up
+    /// other requests. Returns `None` if `render` does.
+    pub fn get_or_render(
+        &self,
+        hash: DocumentHash,
+        options: &RenderOptions,
+        render: impl FnOnce() -> Option<String>,
+    ) -> Option<Arc<str>> {
+        let key = (hash, options.clone());
+
+        if let Some(html) = self.lock().entries.get(&key) {
+            return Some(html.clone());
+        }
+
+        let html: Arc<str> = render()?.into();
+        let mut inner = self.lock();
+
+        if html.len() <= inner.capacity_bytes {
+            inner.size_bytes += html.len();
+            if let Some(previous) = inner.entries.put(key, html.clone()) {
+                inner.size_bytes -= previous.len();
+            }
+
+            while inner.size_bytes > inner.capacity_bytes {
+                match inner.entries.pop_lru() {
+                    Some((_, evicted)) => inner.size_bytes -= evicted.len(),
+                    None => break,
+                }
+            }
+        }
+
+        Some(html)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RenderCacheInner> {
+        // the cache holds no invariants a panicking renderer could break
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The markdown rendering pipeline shared by every HTML endpoint.
+///
+/// Code blocks are optionally replaced by syntect's highlighted HTML, and the
+/// result always goes through [`sanitize_html`], so no render path can emit
+/// markup that the sanitizer has not seen.
+fn render_markdown(markdown_src: &str, highlighting: Option<(&SyntaxSet, &Theme)>) -> String {
     let parser = pulldown_cmark::Parser::new_ext(markdown_src, pulldown_cmark::Options::all());
+
     let mut unsafe_html = String::new();
-    pulldown_cmark::html::push_html(&mut unsafe_html, parser);
-    let html_content = ammonia::Builder::default()
-        .generic_attributes(["id", "name", "class"].into_iter().collect())
-        .clean(&unsafe_html);
+    match highlighting {
+        Some((ss, theme)) => {
+            let events = highlight_code_blocks(parser, ss, theme);
+            pulldown_cmark::html::push_html(&mut unsafe_html, events.into_iter());
+        }
+        None => pulldown_cmark::html::push_html(&mut unsafe_html, parser),
+    }
 
-    html_content.into()
+    sanitize_html(&unsafe_html)
 }
 
-pub fn markdown_to_html_pretty(markdown_src: &str) -> String {
-    let parser = pulldown_cmark::Parser::new_ext(markdown_src, pulldown_cmark::Options::all());
+/// Replaces every code block in `events` with its syntax highlighted HTML.
+fn highlight_code_blocks<'a>(
+    events: impl Iterator<Item = Event<'a>>,
+    ss: &SyntaxSet,
+    theme: &Theme,
+) -> Vec<Event<'a>> {
+    let mut highlighted = Vec::new();
+    let mut code_block: Option<(CodeBlockKind<'a>, String)> = None;
 
-    let mut new_parser = Vec::new();
-    let mut in_code_block = false;
-    let mut to_highlight = String::new();
-    
-    
-    let ss = SyntaxSet::load_defaults_newlines();
-    let ts = ThemeSet::load_defaults();
-    let mut syntax = ss.find_syntax_plain_text();
-    let theme = &ts.themes["InspiredGitHub"];
-
-    for event in parser {
+    for event in events {
         match event {
-            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(l))) => {
-                in_code_block = true;           
-                syntax = ss.find_syntax_by_extension(&l).unwrap_or(ss.find_syntax_plain_text());
+            Event::Start(Tag::CodeBlock(kind)) => code_block = Some((kind, String::new())),
+            Event::Text(text) => match code_block {
+                Some((_, ref mut code)) => code.push_str(&text),
+                None => highlighted.push(Event::Text(text)),
             },
             Event::End(TagEnd::CodeBlock) => {
-                if in_code_block {
-                    let html = highlighted_html_for_string(&to_highlight, &ss, &syntax, &theme).unwrap();
-                    new_parser.push(Event::Html(html.into()));
-                    to_highlight = String::new();
-                    in_code_block = false;
+                let Some((kind, code)) = code_block.take() else {
+                    continue;
+                };
+
+                let syntax = match kind {
+                    CodeBlockKind::Fenced(ref lang) => ss.find_syntax_by_token(lang),
+                    CodeBlockKind::Indented => None,
                 }
-            },
-            Event::Text(t) => {
-                if in_code_block {
-                    to_highlight.push_str(&t);
-                } else {
-                    new_parser.push(Event::Text(t))
+                .unwrap_or_else(|| ss.find_syntax_plain_text());
+
+                match highlighted_html_for_string(&code, ss, syntax, theme) {
+                    Ok(html) => highlighted.push(Event::Html(html.into())),
+                    // fall back to an unhighlighted code block
+                    Err(_) => highlighted.extend([
+                        Event::Start(Tag::CodeBlock(kind)),
+                        Event::Text(code.into()),
+                        Event::End(TagEnd::CodeBlock),
+                    ]),
                 }
-            },
-            e => new_parser.push(e)
+            }
+            e => highlighted.push(e),
+        }
+    }
+
+    highlighted
+}
+
+/// Cleans rendered HTML with an allow-list.
+///
+/// On top of ammonia's defaults, `pre` and `span` may carry the inline styles
+/// syntect emits, but only the declarations listed in [`filter_style`].
+fn sanitize_html(unsafe_html: &str) -> String {
+    ammonia::Builder::default()
+        .generic_attributes(["id", "name", "class"].into_iter().collect())
+        .add_tag_attributes("pre", ["style"].iter())
+        .add_tag_attributes("span", ["style"].iter())
+        .attribute_filter(|_, attribute, value| match attribute {
+            "style" => filter_style(value).map(Into::into),
+            _ => Some(value.into()),
+        })
+        .clean(unsafe_html)
+        .to_string()
+}
+
+/// Keeps only the style declarations syntect uses for highlighting: colors
+/// given as hex codes, bold, italic and underline. Returns `None` if nothing is left.
+fn filter_style(style: &str) -> Option<String> {
+    let is_hex_color = |value: &str| {
+        value.strip_prefix('#').is_some_and(|hex| {
+            matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+        })
+    };
+
+    let filtered: String = style
+        .split(';')
+        .filter_map(|declaration| {
+            let (property, value) = declaration.split_once(':')?;
+            let (property, value) = (property.trim(), value.trim());
+
+            let allowed = match property {
+                "color" | "background-color" => is_hex_color(value),
+                "font-weight" => matches!(value, "bold" | "normal"),
+                "font-style" => matches!(value, "italic" | "normal"),
+                "text-decoration" => matches!(value, "underline" | "none"),
+                _ => false,
+            };
+
+            allowed.then(|| format!("{property}:{value};"))
+        })
+        .collect();
+
+    (!filtered.is_empty()).then_some(filtered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XSS_PAYLOADS: &[&str] = &[
+        "<script>alert(1)</script>",
+        "<img src=x onerror=alert(1)>",
+        "<svg onload=alert(1)>",
+        "<iframe src=\"javascript:alert(1)\"></iframe>",
+        "[click](javascript:alert(1))",
+        "<a href=\"javascript:alert(1)\">click</a>",
+        "<span style=\"background:url(javascript:alert(1))\">x</span>",
+        "<pre style=\"position:fixed;top:0\">x</pre>",
+        "<div onmouseover=\"alert(1)\">x</div>",
+        "```html\n</pre><script>alert(1)</script>\n```",
+        "    </pre><script>alert(1)</script>",
+    ];
+
+    fn assert_clean(html: &str, payload: &str) {
+        let lower = html.to_lowercase();
+        for needle in [
+            "<script",
+            "<iframe",
+            "<svg",
+            "onerror",
+            "onload",
+            "onmouseover",
+        ] {
+            assert!(
+                !lower.contains(needle),
+                "{needle} survived {payload:?}: {html}"
+            );
+        }
+        assert!(
+            !lower.contains("href=\"javascript:"),
+            "link survived {payload:?}: {html}"
+        );
+        assert!(
+            !lower.contains("url("),
+            "css url survived {payload:?}: {html}"
+        );
+        assert!(
+            !lower.contains("position:"),
+            "positioning survived {payload:?}: {html}"
+        );
+    }
+
+    #[test]
+    fn plain_render_removes_xss_payloads() {
+        for payload in XSS_PAYLOADS {
+            assert_clean(&markdown_to_html(payload), payload);
+        }
+    }
+
+    #[test]
+    fn highlighted_render_removes_xss_payloads() {
+        let highlighter = Highlighter::load();
+        for payload in XSS_PAYLOADS {
+            let html = markdown_to_html_pretty(&highlighter, payload, DEFAULT_THEME).unwrap();
+            assert_clean(&html, payload);
         }
     }
 
-    let mut s = String::new();
-    pulldown_cmark::html::push_html(&mut s, new_parser.into_iter());
+    #[test]
+    fn highlighted_render_keeps_syntect_styles() {
+        let html = markdown_to_html_pretty(
+            &Highlighter::load(),
+            "```rs\nfn main() {}\n```",
+            DEFAULT_THEME,
+        )
+        .unwrap();
+        assert!(html.contains("<pre style=\"background-color:#"), "{html}");
+        assert!(html.contains("<span style=\"color:#"), "{html}");
+    }
+
+    #[test]
+    fn highlighted_render_rejects_unknown_theme() {
+        assert!(markdown_to_html_pretty(&Highlighter::load(), "x", "no such theme").is_none());
+    }
 
-    s
-}
\ No newline at end of file
+    #[test]
+    fn render_cache_evicts_least_recently_used_past_capacity() {
+        let cache = RenderCache::new(10);
+        let hash = |n: u8| {
+            DocumentRecord {
+                content: n.to_string(),
+                created: Utc::now(),
+            }
+            .hash()
+        };
+
+        cache.get_or_render(hash(1), &RenderOptions::Plain, || Some("aaaa".into()));
+        cache.get_or_render(hash(2), &RenderOptions::Plain, || Some("bbbb".into()));
+        // touch the first entry so the second one is evicted next
+        cache.get_or_render(hash(1), &RenderOptions::Plain, || unreachable!());
+        cache.get_or_render(hash(3), &RenderOptions::Plain, || Some("cccc".into()));
+
+        let rendered = |n| cache.get_or_render(hash(n), &RenderOptions::Plain, || None);
+        assert_eq!(rendered(1).as_deref(), Some("aaaa"));
+        assert_eq!(rendered(2), None);
+        assert_eq!(rendered(3).as_deref(), Some("cccc"));
+    }
+
+    #[test]
+    fn style_filter_only_keeps_allowed_declarations() {
+        assert_eq!(
+            filter_style("color:#ff0000;font-weight:bold;position:absolute;"),
+            Some("color:#ff0000;font-weight:bold;".into())
+        );
+        assert_eq!(filter_style("color:red"), None);
+        assert_eq!(
+            filter_style("background-color:#fff;background:url(x)"),
+            Some("background-color:#fff;".into())
+        );
+    }
+}
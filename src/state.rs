@@ -1,15 +1,39 @@
-use crate::db::Database;
+use std::sync::Arc;
 
+use crate::{
+    auth::ViewPasswordGuard,
+    blocklist::Blocklist,
+    config::Config,
+    db::Database,
+    services::{Highlighter, RenderCache},
+};
 
 /// Represents the shared state of your application.
 #[derive(Debug, Clone)]
 pub struct AppState {
-    pub db: Database
+    pub config: Arc<Config>,
+    pub db: Database,
+    pub blocklist: Blocklist,
+    pub highlighter: Arc<Highlighter>,
+    pub render_cache: RenderCache,
+    /// Signs the cookies that remember an entered view password. It is made up
+    /// at startup, so a restart asks for view passwords again.
+    pub view_cookie_key: [u8; 32],
+    pub view_passwords: ViewPasswordGuard,
 }
 
 impl AppState {
-    pub fn new(database_path: &str) -> Self {
-        let db = Database::new(database_path).expect("failed to setup database");
-        Self { db }
+    pub fn new(config: Config) -> Self {
+        let db = Database::new(&config.database).expect("failed to setup database");
+        let blocklist = Blocklist::load(&db).expect("failed to load the slug blocklist");
+        Self {
+            db,
+            blocklist,
+            highlighter: Arc::new(Highlighter::load()),
+            render_cache: RenderCache::new(config.render_cache_bytes),
+            view_cookie_key: rand::random(),
+            view_passwords: ViewPasswordGuard::default(),
+            config: Arc::new(config),
+        }
     }
-}
\ No newline at end of file
+}
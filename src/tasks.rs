@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use log::{error, info};
+
+use crate::{
+    db::Database,
+    services::{collect_garbage, reap_expired, GC_GRACE_PERIOD},
+};
+
+/// Spawns a background task that periodically removes orphaned documents.
+///
+/// The first pass runs one `interval` after startup, so a restart loop does
+/// not turn into a loop of full database scans.
+pub fn spawn_garbage_collector(db: Database, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+
+            let db = db.clone();
+            match tokio::task::spawn_blocking(move || collect_garbage(&db, GC_GRACE_PERIOD)).await {
+                Ok(Ok(removed)) => info!("Garbage collection removed {removed} documents"),
+                Ok(Err(e)) => error!("Garbage collection failed: {e}"),
+                Err(e) => error!("Garbage collection task panicked: {e}"),
+            }
+        }
+    });
+}
+
+/// Spawns a background task that periodically deletes expired pastes.
+pub fn spawn_expiry_reaper(db: Database, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            ticker.tick().await;
+
+            let db = db.clone();
+            match tokio::task::spawn_blocking(move || reap_expired(&db)).await {
+                Ok(Ok(0)) => {}
+                Ok(Ok(reaped)) => info!("Expiry reaper deleted {reaped} pastes"),
+                Ok(Err(e)) => error!("Expiry reaper failed: {e}"),
+                Err(e) => error!("Expiry reaper task panicked: {e}"),
+            }
+        }
+    });
+}
+
+/// Spawns a background task that re-encrypts, once, every record still
+/// stored under a previous key or in the clear, so keys can be rotated
+/// without taking the server down.
+pub fn spawn_reencryption(db: Database) {
+    tokio::spawn(async move {
+        match tokio::task::spawn_blocking(move || db.reencrypt()).await {
+            Ok(Ok(0)) => {}
+            Ok(Ok(rewritten)) => info!(
+                "Re-encrypted {rewritten} records, previous encryption keys are no longer needed"
+            ),
+            Ok(Err(e)) => error!("Re-encryption failed: {e}"),
+            Err(e) => error!("Re-encryption task panicked: {e}"),
+        }
+    });
+}
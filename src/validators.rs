@@ -1,64 +1,243 @@
-// pub fn validate_url(url: &str) -> Result<(), ApiError> {
-//     if url.len() < 4 || url.len() > 32 || url.chars().any(|c| !c.is_ascii_alphanumeric()) {
-//         return Err(ApiError {
-//             error_type: ApiErrorType::InvalidUrl,
-//             message: String::from(
-//                 "url must be between 4 and 32 characters and must be ascii alphanumeric",
-//             ),
-//         });
-//     }
-
-//     Ok(())
-// }
-
-// pub fn validate_edit_code(edit_code: &str) -> Result<(), ApiError> {
-//     if  edit_code.len() > 4 ||
-//         edit_code.len() > 32 || 
-//         edit_code.chars().any(|c| !c.is_ascii()) {
-//         return Err(ApiError {
-//             error_type: ApiErrorType::InvalidEditCode,
-//             message: String::from(
-//                 "edit code must be between 4 and 32 characters and must be valid ascii",
-//             ),
-//         });
-//     }
-
-//     Ok(())
-// }
-
-// pub fn validate_document_content(content: &str) -> Result<(), ApiError> {
-//     if content.len() > 200_000 {
-//         return Err(ApiError {
-//             error_type: ApiErrorType::InvalidDocSize,
-//             message: String::from("document must be less than 200,000 bytes"),
-//         });
-//     }
-
-//     Ok(())
-// }
-
-// pub fn validate_create_request(request: &CreatePaste) -> Result<(), ApiError> {
-//     if let Some(ref url) = request.custom_url {
-//         validate_url(url)?;
-//     }
-//     if let Some(ref edit_code) = request.edit_code {
-//         validate_edit_code(edit_code)?;
-//     }
-//     validate_document_content(&request.content)
-// }
-
-pub fn is_invalid_slug(slug: &str) -> bool {
-    slug.len() < 4 ||
-    slug.len() > 32 ||
-    slug.chars().any(|c| !c.is_ascii_alphanumeric())
-}
-
-pub fn is_invalid_edit_code(edit_code: &str) -> bool {
-    edit_code.len() < 4 ||
-    edit_code.len() > 32 ||
-    edit_code.chars().any(|c| !c.is_ascii())
-}
-
-pub fn is_invalid_document(contents: &str) -> bool {
-    contents.len() > 200_000
-}
\ No newline at end of file
+use thiserror::Error;
+
+use crate::{
+    blocklist::is_reserved_slug,
+    config::Limits,
+    envelope::{Envelope, EnvelopeError},
+    routes::api::{ChangeEditCode, CreatePaste, EditPaste, RenamePaste, RenderMarkdown},
+};
+
+/// A rule broken by user input. Messages name the limits that were configured,
+/// so they stay accurate when the limits change.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ValidationError {
+    #[error("slug must be between {min} and {max} characters and ascii alphanumeric")]
+    Slug { min: usize, max: usize },
+
+    #[error("slug is reserved")]
+    ReservedSlug,
+
+    #[error("edit code must be between {min} and {max} characters of printable ascii without spaces")]
+    EditCode { min: usize, max: usize },
+
+    #[error("document must be {max} bytes or less")]
+    DocumentTooLarge { max: usize },
+
+    #[error("edit message must be {max} bytes or less")]
+    EditMessageTooLong { max: usize },
+
+    #[error("burn after reads must be at least 1")]
+    BurnAfterReads,
+
+    #[error("view password must be between {min} and {max} bytes")]
+    ViewPassword { min: usize, max: usize },
+
+    #[error("content of an encrypted paste must be an envelope: {0}")]
+    Envelope(#[from] EnvelopeError),
+}
+
+impl ValidationError {
+    /// The request field this error is about.
+    pub fn field(&self) -> &'static str {
+        match self {
+            ValidationError::Slug { .. } | ValidationError::ReservedSlug => "custom_slug",
+            ValidationError::EditCode { .. } => "edit_code",
+            ValidationError::DocumentTooLarge { .. } | ValidationError::Envelope(_) => "content",
+            ValidationError::EditMessageTooLong { .. } => "message",
+            ValidationError::BurnAfterReads => "burn_after_reads",
+            ValidationError::ViewPassword { .. } => "view_password",
+        }
+    }
+}
+
+/// Input that can be checked against the configured limits before it is used.
+pub trait Validate {
+    fn validate(&self, limits: &Limits) -> Result<(), ValidationError>;
+}
+
+impl Validate for CreatePaste {
+    fn validate(&self, limits: &Limits) -> Result<(), ValidationError> {
+        if let Some(ref slug) = self.custom_slug {
+            validate_slug(slug, limits)?;
+            if is_reserved_slug(slug) {
+                return Err(ValidationError::ReservedSlug);
+            }
+        }
+        if let Some(ref edit_code) = self.edit_code {
+            validate_edit_code(edit_code, limits)?;
+        }
+        validate_document(&self.content, limits)?;
+        if self.encrypted {
+            validate_envelope(&self.content)?;
+        }
+        if let Some(reads) = self.burn_after_reads {
+            validate_burn_after_reads(reads)?;
+        }
+        if let Some(ref password) = self.view_password {
+            validate_view_password(password, limits)?;
+        }
+        Ok(())
+    }
+}
+
+impl Validate for EditPaste {
+    fn validate(&self, limits: &Limits) -> Result<(), ValidationError> {
+        validate_document(&self.content, limits)?;
+        if let Some(ref message) = self.message {
+            validate_edit_message(message, limits)?;
+        }
+        Ok(())
+    }
+}
+
+impl Validate for RenamePaste {
+    fn validate(&self, limits: &Limits) -> Result<(), ValidationError> {
+        validate_slug(&self.new_slug, limits)?;
+        if is_reserved_slug(&self.new_slug) {
+            return Err(ValidationError::ReservedSlug);
+        }
+        Ok(())
+    }
+}
+
+impl Validate for ChangeEditCode {
+    fn validate(&self, limits: &Limits) -> Result<(), ValidationError> {
+        if let Some(ref edit_code) = self.new_edit_code {
+            validate_edit_code(edit_code, limits)?;
+        }
+        Ok(())
+    }
+}
+
+impl Validate for RenderMarkdown {
+    fn validate(&self, limits: &Limits) -> Result<(), ValidationError> {
+        validate_document(&self.content, limits)
+    }
+}
+
+pub fn validate_slug(slug: &str, limits: &Limits) -> Result<(), ValidationError> {
+    let (min, max) = (limits.min_slug_len, limits.max_slug_len);
+    if !(min..=max).contains(&slug.len()) || !slug.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(ValidationError::Slug { min, max });
+    }
+    Ok(())
+}
+
+pub fn validate_edit_code(edit_code: &str, limits: &Limits) -> Result<(), ValidationError> {
+    let (min, max) = (limits.min_edit_code_len, limits.max_edit_code_len);
+    if !(min..=max).contains(&edit_code.len()) || !edit_code.chars().all(|c| c.is_ascii_graphic())
+    {
+        return Err(ValidationError::EditCode { min, max });
+    }
+    Ok(())
+}
+
+pub fn validate_document(content: &str, limits: &Limits) -> Result<(), ValidationError> {
+    if content.len() > limits.max_document_bytes {
+        return Err(ValidationError::DocumentTooLarge {
+            max: limits.max_document_bytes,
+        });
+    }
+    Ok(())
+}
+
+pub fn validate_edit_message(message: &str, limits: &Limits) -> Result<(), ValidationError> {
+    if message.len() > limits.max_edit_message_bytes {
+        return Err(ValidationError::EditMessageTooLong {
+            max: limits.max_edit_message_bytes,
+        });
+    }
+    Ok(())
+}
+
+pub fn validate_burn_after_reads(reads: u32) -> Result<(), ValidationError> {
+    if reads == 0 {
+        return Err(ValidationError::BurnAfterReads);
+    }
+    Ok(())
+}
+
+pub fn validate_envelope(content: &str) -> Result<(), ValidationError> {
+    Envelope::parse(content)?;
+    Ok(())
+}
+
+pub fn validate_view_password(password: &str, limits: &Limits) -> Result<(), ValidationError> {
+    let (min, max) = (limits.min_view_password_len, limits.max_view_password_len);
+    if !(min..=max).contains(&password.len()) {
+        return Err(ValidationError::ViewPassword { min, max });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn limits() -> impl Strategy<Value = Limits> {
+        (1usize..16, 0usize..16, 0usize..512).prop_map(|(min, extra, max_bytes)| Limits {
+            min_slug_len: min,
+            max_slug_len: min + extra,
+            min_edit_code_len: min,
+            max_edit_code_len: min + extra,
+            max_document_bytes: max_bytes,
+            max_edit_message_bytes: max_bytes,
+            ..Limits::default()
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn slugs_within_limits_are_accepted(limits in limits(), seed in "[a-zA-Z0-9]{40}", len in 0usize..40) {
+            let slug = &seed[..len];
+            let in_range = (limits.min_slug_len..=limits.max_slug_len).contains(&len);
+            prop_assert_eq!(validate_slug(slug, &limits).is_ok(), in_range);
+        }
+
+        #[test]
+        fn slugs_with_other_characters_are_rejected(limits in limits(), slug in ".*[^a-zA-Z0-9].*") {
+            prop_assert!(validate_slug(&slug, &limits).is_err());
+        }
+
+        #[test]
+        fn edit_codes_without_whitespace_or_control_characters_are_accepted(
+            limits in limits(),
+            seed in "[!-~]{40}",
+            len in 0usize..40,
+        ) {
+            let edit_code = &seed[..len];
+            let in_range = (limits.min_edit_code_len..=limits.max_edit_code_len).contains(&len);
+            prop_assert_eq!(validate_edit_code(edit_code, &limits).is_ok(), in_range);
+        }
+
+        #[test]
+        fn edit_codes_with_whitespace_or_control_characters_are_rejected(
+            limits in limits(),
+            prefix in "[!-~]{0,8}",
+            bad in "[\\x00-\\x20\\x7f]",
+            suffix in "[!-~]{0,8}",
+        ) {
+            let edit_code = format!("{prefix}{bad}{suffix}");
+            prop_assert!(validate_edit_code(&edit_code, &limits).is_err());
+        }
+
+        #[test]
+        fn documents_are_limited_in_bytes(limits in limits(), content in "\\PC{0,300}") {
+            let result = validate_document(&content, &limits);
+            prop_assert_eq!(result.is_ok(), content.len() <= limits.max_document_bytes);
+        }
+
+        #[test]
+        fn messages_name_the_configured_limits(limits in limits(), slug in "[^a-zA-Z0-9]{1,4}") {
+            let message = validate_slug(&slug, &limits).unwrap_err().to_string();
+            let expected = format!("between {} and {}", limits.min_slug_len, limits.max_slug_len);
+            prop_assert!(message.contains(&expected));
+
+            let too_long = "x".repeat(limits.max_document_bytes + 1);
+            let message = validate_document(&too_long, &limits).unwrap_err().to_string();
+            prop_assert!(message.contains(&limits.max_document_bytes.to_string()));
+        }
+    }
+}
@@ -144,4 +144,4 @@ class ApiClient {
 
 // Assuming you have an instance of ApiClient available in your scripts
 // You can create an instance like so:
-const apiClient = new ApiClient('http://localhost:3000/api');
\ No newline at end of file
+const apiClient = new ApiClient('/api');
\ No newline at end of file
@@ -0,0 +1,386 @@
+// Argon2id (version 0x13) as specified in RFC 9106, with BLAKE2b from RFC 7693,
+// for the page that shows client-side encrypted pastes. There is no secret and
+// no associated data, and lanes are filled one after another.
+//
+// 64 bit words are kept as pairs of 32 bit halves, low half first, in
+// Uint32Arrays, which wrap around on assignment like the arithmetic needs.
+
+const TWO_32 = 0x100000000;
+
+// Adds the words at `a` and `b` of `v` into `a`.
+function add64(v, a, b) {
+    const lo = v[a] + v[b];
+    v[a + 1] = v[a + 1] + v[b + 1] + (lo >= TWO_32 ? 1 : 0);
+    v[a] = lo;
+}
+
+// Adds the word `lo`, `hi` into `a`.
+function add64Constant(v, a, lo, hi) {
+    const sum = v[a] + lo;
+    v[a + 1] = v[a + 1] + hi + (sum >= TWO_32 ? 1 : 0);
+    v[a] = sum;
+}
+
+// Sets the word at `x` to itself xor the word at `y`, rotated right by `n`.
+function xorRotate(v, x, y, n) {
+    const lo = v[x] ^ v[y];
+    const hi = v[x + 1] ^ v[y + 1];
+    if (n === 32) {
+        v[x] = hi;
+        v[x + 1] = lo;
+    } else if (n < 32) {
+        v[x] = (lo >>> n) | (hi << (32 - n));
+        v[x + 1] = (hi >>> n) | (lo << (32 - n));
+    } else {
+        v[x] = (hi >>> (n - 32)) | (lo << (64 - n));
+        v[x + 1] = (lo >>> (n - 32)) | (hi << (64 - n));
+    }
+}
+
+// BLAKE2b
+
+const BLAKE2B_IV = new Uint32Array([
+    0xf3bcc908, 0x6a09e667, 0x84caa73b, 0xbb67ae85, 0xfe94f82b, 0x3c6ef372, 0x5f1d36f1, 0xa54ff53a,
+    0xade682d1, 0x510e527f, 0x2b3e6c1f, 0x9b05688c, 0xfb41bd6b, 0x1f83d9ab, 0x137e2179, 0x5be0cd19,
+]);
+
+const SIGMA = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+];
+
+function blake2bMix(v, m, a, b, c, d, x, y) {
+    add64(v, a, b);
+    add64Constant(v, a, m[x], m[x + 1]);
+    xorRotate(v, d, a, 32);
+    add64(v, c, d);
+    xorRotate(v, b, c, 24);
+    add64(v, a, b);
+    add64Constant(v, a, m[y], m[y + 1]);
+    xorRotate(v, d, a, 16);
+    add64(v, c, d);
+    xorRotate(v, b, c, 63);
+}
+
+function blake2bCompress(h, block, counter, last) {
+    const v = new Uint32Array(32);
+    v.set(h);
+    v.set(BLAKE2B_IV, 16);
+    v[24] ^= counter % TWO_32;
+    v[25] ^= Math.floor(counter / TWO_32);
+    if (last) {
+        v[28] = ~v[28];
+        v[29] = ~v[29];
+    }
+
+    const m = new Uint32Array(32);
+    for (let i = 0; i < 32; i++) {
+        m[i] = block[4 * i] | (block[4 * i + 1] << 8) | (block[4 * i + 2] << 16) | (block[4 * i + 3] << 24);
+    }
+
+    for (const s of SIGMA) {
+        blake2bMix(v, m, 0, 8, 16, 24, 2 * s[0], 2 * s[1]);
+        blake2bMix(v, m, 2, 10, 18, 26, 2 * s[2], 2 * s[3]);
+        blake2bMix(v, m, 4, 12, 20, 28, 2 * s[4], 2 * s[5]);
+        blake2bMix(v, m, 6, 14, 22, 30, 2 * s[6], 2 * s[7]);
+        blake2bMix(v, m, 0, 10, 20, 30, 2 * s[8], 2 * s[9]);
+        blake2bMix(v, m, 2, 12, 22, 24, 2 * s[10], 2 * s[11]);
+        blake2bMix(v, m, 4, 14, 16, 26, 2 * s[12], 2 * s[13]);
+        blake2bMix(v, m, 6, 8, 18, 28, 2 * s[14], 2 * s[15]);
+    }
+
+    for (let i = 0; i < 16; i++) {
+        h[i] ^= v[i] ^ v[i + 16];
+    }
+}
+
+// Unkeyed BLAKE2b of the concatenated `parts` with an `outLen` byte digest.
+function blake2b(parts, outLen) {
+    const h = BLAKE2B_IV.slice();
+    h[0] ^= 0x01010000 ^ outLen;
+
+    const length = parts.reduce((total, part) => total + part.length, 0);
+    const input = new Uint8Array(Math.max(128, Math.ceil(length / 128) * 128));
+    let offset = 0;
+    for (const part of parts) {
+        input.set(part, offset);
+        offset += part.length;
+    }
+
+    const blocks = input.length / 128;
+    for (let i = 0; i < blocks; i++) {
+        const last = i === blocks - 1;
+        const counter = last ? length : (i + 1) * 128;
+        blake2bCompress(h, input.subarray(128 * i, 128 * (i + 1)), counter, last);
+    }
+
+    const out = new Uint8Array(outLen);
+    for (let i = 0; i < outLen; i++) {
+        out[i] = h[i >> 2] >>> (8 * (i & 3));
+    }
+    return out;
+}
+
+function uint32(n) {
+    return new Uint8Array([n, n >>> 8, n >>> 16, n >>> 24]);
+}
+
+// The variable length hash H' of RFC 9106, section 3.3.
+function blake2bLong(parts, outLen) {
+    const lengthPrefix = uint32(outLen);
+    if (outLen <= 64) {
+        return blake2b([lengthPrefix, ...parts], outLen);
+    }
+
+    const out = new Uint8Array(outLen);
+    const blocks = Math.ceil(outLen / 32) - 2;
+    let v = blake2b([lengthPrefix, ...parts], 64);
+    out.set(v.subarray(0, 32));
+    for (let i = 1; i < blocks; i++) {
+        v = blake2b([v], 64);
+        out.set(v.subarray(0, 32), 32 * i);
+    }
+    out.set(blake2b([v], outLen - 32 * blocks), 32 * blocks);
+    return out;
+}
+
+// Argon2
+
+const BLOCK_WORDS = 256; // 1024 byte blocks, as 32 bit halves
+const SYNC_POINTS = 4;
+const ARGON2_VERSION = 0x13;
+const ARGON2ID = 2;
+
+// Sets the word at `a` to a + b + 2 * lo(a) * lo(b), the BlaMka multiplication.
+function blaMka(v, a, b) {
+    const x = v[a];
+    const y = v[b];
+    const xl = x & 0xffff;
+    const xh = x >>> 16;
+    const yl = y & 0xffff;
+    const yh = y >>> 16;
+    const middle = xl * yh + xh * yl;
+    const productLo = xl * yl + (middle % 0x10000) * 0x10000;
+    const productHi = xh * yh + Math.floor(middle / 0x10000) + Math.floor(productLo / TWO_32);
+
+    const doubledLo = (productLo << 1) >>> 0;
+    const doubledHi = ((productHi << 1) | ((productLo >>> 31) & 1)) >>> 0;
+    const lo = x + v[b] + doubledLo;
+    v[a + 1] = v[a + 1] + v[b + 1] + doubledHi + Math.floor(lo / TWO_32);
+    v[a] = lo;
+}
+
+function argonMix(v, a, b, c, d) {
+    blaMka(v, a, b);
+    xorRotate(v, d, a, 32);
+    blaMka(v, c, d);
+    xorRotate(v, b, c, 24);
+    blaMka(v, a, b);
+    xorRotate(v, d, a, 16);
+    blaMka(v, c, d);
+    xorRotate(v, b, c, 63);
+}
+
+// The BLAKE2b round without a message, over the 16 words at `w` (halves indices).
+function permute(v, w) {
+    argonMix(v, w[0], w[4], w[8], w[12]);
+    argonMix(v, w[1], w[5], w[9], w[13]);
+    argonMix(v, w[2], w[6], w[10], w[14]);
+    argonMix(v, w[3], w[7], w[11], w[15]);
+    argonMix(v, w[0], w[5], w[10], w[15]);
+    argonMix(v, w[1], w[6], w[11], w[12]);
+    argonMix(v, w[2], w[7], w[8], w[13]);
+    argonMix(v, w[3], w[4], w[9], w[14]);
+}
+
+// The permutation works on the 8 rows of 16 words, then on the 8 columns of pairs of words.
+const ROWS = [];
+const COLUMNS = [];
+for (let i = 0; i < 8; i++) {
+    const row = [];
+    const column = [];
+    for (let j = 0; j < 16; j++) {
+        row.push(2 * (16 * i + j));
+        column.push(2 * (2 * i + 16 * (j >> 1) + (j & 1)));
+    }
+    ROWS.push(row);
+    COLUMNS.push(column);
+}
+
+// The compression function G: writes G(x, y) into `out` at `outOffset`, xored
+// with what is already there when `xor` is set.
+function compress(x, xOffset, y, yOffset, out, outOffset, xor) {
+    const r = new Uint32Array(BLOCK_WORDS);
+    for (let i = 0; i < BLOCK_WORDS; i++) {
+        r[i] = x[xOffset + i] ^ y[yOffset + i];
+    }
+    const z = r.slice();
+    for (const row of ROWS) {
+        permute(z, row);
+    }
+    for (const column of COLUMNS) {
+        permute(z, column);
+    }
+
+    for (let i = 0; i < BLOCK_WORDS; i++) {
+        const value = z[i] ^ r[i];
+        out[outOffset + i] = xor ? out[outOffset + i] ^ value : value;
+    }
+}
+
+function blockToBytes(memory, offset) {
+    const bytes = new Uint8Array(1024);
+    for (let i = 0; i < BLOCK_WORDS; i++) {
+        const word = memory[offset + i];
+        bytes[4 * i] = word;
+        bytes[4 * i + 1] = word >>> 8;
+        bytes[4 * i + 2] = word >>> 16;
+        bytes[4 * i + 3] = word >>> 24;
+    }
+    return bytes;
+}
+
+function bytesToBlock(bytes, memory, offset) {
+    for (let i = 0; i < BLOCK_WORDS; i++) {
+        memory[offset + i] = bytes[4 * i] | (bytes[4 * i + 1] << 8) | (bytes[4 * i + 2] << 16) | (bytes[4 * i + 3] << 24);
+    }
+}
+
+// The high 32 bits of the product of two 32 bit integers.
+function mulHi(x, y) {
+    const xl = x & 0xffff;
+    const xh = x >>> 16;
+    const yl = y & 0xffff;
+    const yh = y >>> 16;
+    const middle = xl * yh + xh * yl;
+    const lo = xl * yl + (middle % 0x10000) * 0x10000;
+    return xh * yh + Math.floor(middle / 0x10000) + Math.floor(lo / TWO_32);
+}
+
+export function argon2id(password, salt, { m, t, p, dkLen = 32 }) {
+    if (typeof password === 'string') {
+        password = new TextEncoder().encode(password);
+    }
+    if (!(p >= 1 && m >= 8 * p && t >= 1)) {
+        throw new Error('argon2id parameters are out of range');
+    }
+
+    const h0 = blake2b([
+        uint32(p), uint32(dkLen), uint32(m), uint32(t), uint32(ARGON2_VERSION), uint32(ARGON2ID),
+        uint32(password.length), password,
+        uint32(salt.length), salt,
+        uint32(0), uint32(0),
+    ], 64);
+
+    const segmentLength = Math.floor(m / (SYNC_POINTS * p));
+    const laneLength = segmentLength * SYNC_POINTS;
+    const blockCount = laneLength * p;
+    const memory = new Uint32Array(blockCount * BLOCK_WORDS);
+    const blockOffset = (lane, index) => (lane * laneLength + index) * BLOCK_WORDS;
+
+    for (let lane = 0; lane < p; lane++) {
+        for (let i = 0; i < 2; i++) {
+            const block = blake2bLong([h0, uint32(i), uint32(lane)], 1024);
+            bytesToBlock(block, memory, blockOffset(lane, i));
+        }
+    }
+
+    const zero = new Uint32Array(BLOCK_WORDS);
+    const input = new Uint32Array(BLOCK_WORDS);
+    const scratch = new Uint32Array(BLOCK_WORDS);
+    const addresses = new Uint32Array(BLOCK_WORDS);
+    const nextAddresses = () => {
+        input[12]++;
+        compress(zero, 0, input, 0, scratch, 0, false);
+        compress(zero, 0, scratch, 0, addresses, 0, false);
+    };
+
+    for (let pass = 0; pass < t; pass++) {
+        for (let slice = 0; slice < SYNC_POINTS; slice++) {
+            for (let lane = 0; lane < p; lane++) {
+                // Argon2id addresses independently of the data for the first half of the first pass
+                const independent = pass === 0 && slice < 2;
+                if (independent) {
+                    input.fill(0);
+                    input[0] = pass;
+                    input[2] = lane;
+                    input[4] = slice;
+                    input[6] = blockCount;
+                    input[8] = t;
+                    input[10] = ARGON2ID;
+                }
+
+                let start = 0;
+                if (pass === 0 && slice === 0) {
+                    start = 2;
+                    if (independent) {
+                        nextAddresses();
+                    }
+                }
+
+                for (let index = start; index < segmentLength; index++) {
+                    const position = slice * segmentLength + index;
+                    const previous = blockOffset(lane, position === 0 ? laneLength - 1 : position - 1);
+
+                    let j1;
+                    let j2;
+                    if (independent) {
+                        if (index % 128 === 0) {
+                            nextAddresses();
+                        }
+                        j1 = addresses[2 * (index % 128)];
+                        j2 = addresses[2 * (index % 128) + 1];
+                    } else {
+                        j1 = memory[previous];
+                        j2 = memory[previous + 1];
+                    }
+
+                    const refLane = pass === 0 && slice === 0 ? lane : j2 % p;
+                    const sameLane = refLane === lane;
+                    let areaSize;
+                    if (pass === 0) {
+                        areaSize = sameLane
+                            ? slice * segmentLength + index - 1
+                            : slice * segmentLength + (index === 0 ? -1 : 0);
+                    } else {
+                        areaSize = sameLane
+                            ? laneLength - segmentLength + index - 1
+                            : laneLength - segmentLength + (index === 0 ? -1 : 0);
+                    }
+
+                    const x = mulHi(j1, j1);
+                    const y = Math.floor((areaSize * x) / TWO_32);
+                    const relative = areaSize - 1 - y;
+                    const areaStart = pass === 0 || slice === SYNC_POINTS - 1 ? 0 : (slice + 1) * segmentLength;
+                    const refIndex = (areaStart + relative) % laneLength;
+
+                    compress(
+                        memory, previous,
+                        memory, blockOffset(refLane, refIndex),
+                        memory, blockOffset(lane, position),
+                        pass > 0,
+                    );
+                }
+            }
+        }
+    }
+
+    const last = new Uint32Array(BLOCK_WORDS);
+    for (let lane = 0; lane < p; lane++) {
+        const offset = blockOffset(lane, laneLength - 1);
+        for (let i = 0; i < BLOCK_WORDS; i++) {
+            last[i] ^= memory[offset + i];
+        }
+    }
+    return blake2bLong([blockToBytes(last, 0)], dkLen);
+}
@@ -0,0 +1,144 @@
+// ChaCha20-Poly1305 decryption as specified in RFC 8439, for the page that
+// shows client-side encrypted pastes. Only what that page needs is here: a
+// 32 byte key, a 12 byte nonce and no associated data.
+
+const SIGMA = [0x61707865, 0x3320646e, 0x79622d32, 0x6b206574]; // "expand 32-byte k"
+
+function readUint32(bytes, offset) {
+    return (bytes[offset] | (bytes[offset + 1] << 8) | (bytes[offset + 2] << 16) | (bytes[offset + 3] << 24)) >>> 0;
+}
+
+function rotl(x, n) {
+    return (x << n) | (x >>> (32 - n));
+}
+
+function quarterRound(x, a, b, c, d) {
+    x[a] += x[b]; x[d] = rotl(x[d] ^ x[a], 16);
+    x[c] += x[d]; x[b] = rotl(x[b] ^ x[c], 12);
+    x[a] += x[b]; x[d] = rotl(x[d] ^ x[a], 8);
+    x[c] += x[d]; x[b] = rotl(x[b] ^ x[c], 7);
+}
+
+// Writes the 64 byte keystream block for `counter` into `out`.
+function chachaBlock(key, counter, nonce, out) {
+    const state = new Uint32Array(16);
+    state.set(SIGMA);
+    for (let i = 0; i < 8; i++) {
+        state[4 + i] = readUint32(key, 4 * i);
+    }
+    state[12] = counter;
+    for (let i = 0; i < 3; i++) {
+        state[13 + i] = readUint32(nonce, 4 * i);
+    }
+
+    const x = state.slice();
+    for (let round = 0; round < 10; round++) {
+        quarterRound(x, 0, 4, 8, 12);
+        quarterRound(x, 1, 5, 9, 13);
+        quarterRound(x, 2, 6, 10, 14);
+        quarterRound(x, 3, 7, 11, 15);
+        quarterRound(x, 0, 5, 10, 15);
+        quarterRound(x, 1, 6, 11, 12);
+        quarterRound(x, 2, 7, 8, 13);
+        quarterRound(x, 3, 4, 9, 14);
+    }
+
+    for (let i = 0; i < 16; i++) {
+        const word = (x[i] + state[i]) >>> 0;
+        out[4 * i] = word;
+        out[4 * i + 1] = word >>> 8;
+        out[4 * i + 2] = word >>> 16;
+        out[4 * i + 3] = word >>> 24;
+    }
+}
+
+function chacha20(key, nonce, counter, input) {
+    const output = new Uint8Array(input.length);
+    const block = new Uint8Array(64);
+    for (let offset = 0; offset < input.length; offset += 64, counter++) {
+        chachaBlock(key, counter, nonce, block);
+        const end = Math.min(64, input.length - offset);
+        for (let i = 0; i < end; i++) {
+            output[offset + i] = input[offset + i] ^ block[i];
+        }
+    }
+    return output;
+}
+
+function littleEndian(bytes) {
+    let n = 0n;
+    for (let i = bytes.length - 1; i >= 0; i--) {
+        n = (n << 8n) | BigInt(bytes[i]);
+    }
+    return n;
+}
+
+const P1305 = (1n << 130n) - 5n;
+
+function poly1305(key, message) {
+    const r = littleEndian(key.subarray(0, 16)) & 0x0ffffffc0ffffffc0ffffffc0fffffffn;
+    const s = littleEndian(key.subarray(16, 32));
+
+    let accumulator = 0n;
+    for (let offset = 0; offset < message.length; offset += 16) {
+        const chunk = message.subarray(offset, offset + 16);
+        const n = littleEndian(chunk) | (1n << BigInt(8 * chunk.length));
+        accumulator = ((accumulator + n) * r) % P1305;
+    }
+    accumulator = (accumulator + s) & ((1n << 128n) - 1n);
+
+    const tag = new Uint8Array(16);
+    for (let i = 0; i < 16; i++) {
+        tag[i] = Number(accumulator & 0xffn);
+        accumulator >>= 8n;
+    }
+    return tag;
+}
+
+// The Poly1305 input for an empty AAD: the ciphertext padded to 16 bytes,
+// then the AAD and ciphertext lengths as 64 bit little-endian integers.
+function macData(ciphertext) {
+    const padded = Math.ceil(ciphertext.length / 16) * 16;
+    const data = new Uint8Array(padded + 16);
+    data.set(ciphertext);
+    let length = ciphertext.length;
+    for (let i = 0; i < 8; i++) {
+        data[padded + 8 + i] = length % 256;
+        length = Math.floor(length / 256);
+    }
+    return data;
+}
+
+export function chacha20poly1305(key, nonce) {
+    if (key.length !== 32) {
+        throw new Error('key must be 32 bytes');
+    }
+    if (nonce.length !== 12) {
+        throw new Error('nonce must be 12 bytes');
+    }
+
+    return {
+        // Checks the tag at the end of `sealed` and returns the plaintext.
+        decrypt(sealed) {
+            if (sealed.length < 16) {
+                throw new Error('ciphertext is shorter than its tag');
+            }
+            const ciphertext = sealed.subarray(0, sealed.length - 16);
+            const tag = sealed.subarray(sealed.length - 16);
+
+            const polyKey = new Uint8Array(64);
+            chachaBlock(key, 0, nonce, polyKey);
+            const expected = poly1305(polyKey.subarray(0, 32), macData(ciphertext));
+
+            let difference = 0;
+            for (let i = 0; i < 16; i++) {
+                difference |= expected[i] ^ tag[i];
+            }
+            if (difference !== 0) {
+                throw new Error('invalid tag');
+            }
+
+            return chacha20(key, nonce, 1, ciphertext);
+        },
+    };
+}